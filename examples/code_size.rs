@@ -0,0 +1,115 @@
+//! Measures the code saved by sharing the backend's slow path between initializers.
+//!
+//! Each cell below is a [`OnceLock`] over [`RawSpinOnce`], initialized by a closure of its
+//! own type. [`OnceLock::get_or_init`] runs every closure through the backend's one
+//! [`RawOnce::call_dyn`](sync_api::RawOnce::call_dyn). [`OnceLock::get_or_init_timeout`]
+//! instead calls the backend's generic [`RawOnceTimed::call_until`], which is
+//! instantiated once per closure. Given a deadline that never passes, it does the same
+//! work, plus checking the deadline while it spins.
+//!
+//! Run without arguments, the example builds itself twice in release mode, once with each
+//! method, strips the symbols that would otherwise dominate the difference, and prints the
+//! size of both binaries:
+//!
+//! ```text
+//! cargo run --features spin --example code_size
+//! ```
+//!
+//! [`RawOnceTimed::call_until`]: sync_api::RawOnceTimed::call_until
+
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    process::Command,
+    time::Duration,
+};
+
+use sync_api::{spin::RawSpinOnce, Deadline, OnceLock, Unidentified};
+
+/// Which method the cells are initialized with, set by the outer run for each build.
+const VARIANT: Option<&str> = option_env!("CODE_SIZE_VARIANT");
+
+type Cell = OnceLock<RawSpinOnce<Unidentified>, usize>;
+
+/// A deadline that never passes.
+struct Never;
+
+impl Deadline for Never {
+    fn remaining(&self) -> Option<Duration> {
+        Some(Duration::MAX)
+    }
+}
+
+#[inline(never)]
+fn touch<const N: usize>(cell: &Cell) -> usize {
+    if VARIANT == Some("generic") {
+        *cell.get_or_init_timeout(|| N * 3, Never).unwrap()
+    } else {
+        *cell.get_or_init(|| N * 3)
+    }
+}
+
+macro_rules! touch_all {
+    ($cells:ident, $($n:literal)*) => {
+        0 $(+ touch::<$n>(&$cells[$n]))*
+    };
+}
+
+fn run() {
+    let cells: [Cell; 64] = std::array::from_fn(|_| Cell::new());
+    let sum = touch_all!(
+        cells, 0 1 2 3 4 5 6 7 8 9 10 11 12 13 14 15 16 17 18 19 20 21 22 23 24 25 26 27 28
+        29 30 31 32 33 34 35 36 37 38 39 40 41 42 43 44 45 46 47 48 49 50 51 52 53 54 55 56
+        57 58 59 60 61 62 63
+    );
+    assert_eq!(sum, 3 * (0..64).sum::<usize>());
+}
+
+/// Builds the example with `variant` and returns the size of the binary.
+fn build(variant: &str, target_dir: &Path) -> u64 {
+    let cargo = env::var_os("CARGO").unwrap_or_else(|| "cargo".into());
+    let status = Command::new(cargo)
+        .args(["build", "--quiet", "--release", "--features", "spin"])
+        .args([
+            "--example",
+            "code_size",
+            "--config",
+            "profile.release.strip=true",
+        ])
+        .arg("--target-dir")
+        .arg(target_dir)
+        .env("CODE_SIZE_VARIANT", variant)
+        .status()
+        .expect("could not run cargo");
+    assert!(status.success(), "building the {variant} variant failed");
+
+    let binary = target_dir
+        .join("release/examples/code_size")
+        .with_extension(env::consts::EXE_EXTENSION);
+    fs::metadata(binary)
+        .expect("could not read the size of the binary")
+        .len()
+}
+
+fn main() {
+    if VARIANT.is_some() {
+        run();
+        return;
+    }
+
+    // Separate target directories, so that neither build waits on the lock of the one
+    // running this.
+    let target_dir = env::current_exe()
+        .ok()
+        .and_then(|exe| Some(exe.parent()?.parent()?.parent()?.join("code_size")))
+        .unwrap_or_else(|| PathBuf::from("target/code_size"));
+    let shared = build("shared", &target_dir.join("shared"));
+    let generic = build("generic", &target_dir.join("generic"));
+
+    println!("shared slow path:  {shared} bytes");
+    println!("generic slow path: {generic} bytes");
+    println!(
+        "sharing saves {} bytes over 64 initializers",
+        generic as i64 - shared as i64
+    );
+}
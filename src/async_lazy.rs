@@ -0,0 +1,58 @@
+use core::{cell::UnsafeCell, future::Future};
+
+use crate::{AsyncOnceLock, RawOnce};
+
+/// A value that is initialized by a future on first access.
+///
+/// If the future initializing the value is dropped before it finishes, the next task to
+/// access the value calls `init` again, so it is an [`FnMut`] rather than an [`FnOnce`].
+///
+/// The value is kept in an [`AsyncOnceLock`], and the same caveat applies: without the
+/// `critical-section` feature it must not be used from contexts that can preempt each
+/// other.
+pub struct AsyncLazy<R, T, F>
+where
+    R: RawOnce,
+{
+    cell: AsyncOnceLock<R, T>,
+    init: UnsafeCell<F>,
+}
+
+impl<R, T, F> AsyncLazy<R, T, F>
+where
+    R: RawOnce,
+{
+    pub const fn new(init: F) -> Self {
+        Self {
+            cell: AsyncOnceLock::new(),
+            init: UnsafeCell::new(init),
+        }
+    }
+}
+
+impl<R, T, F, Fut> AsyncLazy<R, T, F>
+where
+    R: RawOnce,
+    F: FnMut() -> Fut,
+    Fut: Future<Output = T>,
+{
+    pub async fn force(&self) -> &T {
+        // Only the task that moved the cell into the running state calls the initializer,
+        // so it has exclusive access to `init`.
+        self.cell
+            .get_or_init(|| unsafe { (*self.init.get())() })
+            .await
+    }
+
+    pub fn get(&self) -> Option<&T> {
+        self.cell.get()
+    }
+}
+
+unsafe impl<R, T, F> Sync for AsyncLazy<R, T, F>
+where
+    T: Sync + Send,
+    R: RawOnce + Sync + Send,
+    F: Send,
+{
+}
@@ -0,0 +1,255 @@
+use core::{
+    convert::Infallible,
+    fmt::Debug,
+    future::Future,
+    mem,
+    pin::Pin,
+    sync::atomic::Ordering,
+    task::{Context, Poll},
+};
+
+use crate::{
+    into_ok,
+    state::{AtomicState, State},
+    waker_list::{Waiter, WakerList},
+    OnceLock, RawOnce,
+};
+
+/// A [`OnceLock`] that is initialized by a future.
+///
+/// Tasks that find the cell being initialized are suspended instead of blocking the
+/// executor, and woken once it is done. Any executor works, as only [`core::task`] is used.
+///
+/// Initialization follows the same four states as a [`RawOnce`]. If the initializing
+/// future fails, panics or is dropped before it finishes, the cell is left poisoned and the
+/// waiting tasks are woken so that one of them can take over. Like [`OnceLock`], the cell
+/// itself ignores poisoning.
+///
+/// The value is stored in a [`OnceLock<R, T>`], so `R` is only ever called uncontended to
+/// publish it.
+///
+/// Waiting tasks are queued under a lock. Without the `critical-section` feature it is a
+/// spin lock, so the cell must not be used from contexts that can preempt each other, such
+/// as executors running at different interrupt priorities on one core, or an executor and
+/// an interrupt handler. With the feature, the queue is locked with a critical section.
+pub struct AsyncOnceLock<R, T>
+where
+    R: RawOnce,
+{
+    cell: OnceLock<R, T>,
+    state: AtomicState,
+    wakers: WakerList,
+}
+
+impl<R, T> AsyncOnceLock<R, T>
+where
+    R: RawOnce,
+{
+    pub const fn new() -> Self {
+        Self {
+            cell: OnceLock::new(),
+            state: AtomicState::new(State::Incomplete),
+            wakers: WakerList::new(),
+        }
+    }
+
+    pub const fn with_value(value: T) -> Self {
+        Self {
+            cell: OnceLock::with_value(value),
+            state: AtomicState::new(State::Complete),
+            wakers: WakerList::new(),
+        }
+    }
+
+    pub fn get(&self) -> Option<&T> {
+        self.cell.get()
+    }
+
+    pub fn get_mut(&mut self) -> Option<&mut T> {
+        self.cell.get_mut()
+    }
+
+    pub fn into_inner(self) -> Option<T> {
+        self.cell.into_inner()
+    }
+
+    pub async fn get_or_init<F, Fut>(&self, f: F) -> &T
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = T>,
+    {
+        let res = self
+            .get_or_try_init(|| async move { Ok::<_, Infallible>(f().await) })
+            .await;
+        into_ok(res)
+    }
+
+    pub async fn get_or_try_init<F, Fut, E>(&self, f: F) -> Result<&T, E>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<T, E>>,
+    {
+        if let Some(value) = self.get() {
+            return Ok(value);
+        }
+
+        let acquire = Acquire {
+            lock: self,
+            waiter: Waiter::new(&self.wakers),
+        };
+        if let Some(guard) = acquire.await {
+            let value = f().await?;
+            guard.complete(value);
+        }
+        Ok(unsafe { self.cell.get_unchecked() })
+    }
+
+    /// Wait until the cell has been initialized, without supplying an initializer.
+    pub async fn wait(&self) -> &T {
+        if let Some(value) = self.get() {
+            return value;
+        }
+
+        Wait {
+            lock: self,
+            waiter: Waiter::new(&self.wakers),
+        }
+        .await;
+        unsafe { self.cell.get_unchecked() }
+    }
+}
+
+impl<R, T> Debug for AsyncOnceLock<R, T>
+where
+    R: RawOnce,
+    T: Debug,
+{
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("AsyncOnceLock")
+            .field("value", &self.get())
+            .finish()
+    }
+}
+
+impl<R, T> Default for AsyncOnceLock<R, T>
+where
+    R: RawOnce,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Resolves to a guard once the caller may run the initializer, or to `None` once someone
+/// else has.
+struct Acquire<'a, R, T>
+where
+    R: RawOnce,
+{
+    lock: &'a AsyncOnceLock<R, T>,
+    waiter: Waiter<'a>,
+}
+
+impl<'a, R, T> Future for Acquire<'a, R, T>
+where
+    R: RawOnce,
+{
+    type Output = Option<Guard<'a, R, T>>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let lock = self.lock;
+        let waiter = unsafe { self.as_ref().map_unchecked(|acquire| &acquire.waiter) };
+        let mut registered = false;
+
+        loop {
+            let state = lock.state.load(Ordering::Acquire);
+            match state {
+                State::Complete => return Poll::Ready(None),
+                State::Incomplete | State::Poisoned => {
+                    let exchange = lock.state.compare_exchange(
+                        state,
+                        State::Running,
+                        Ordering::Acquire,
+                        Ordering::Relaxed,
+                    );
+                    if exchange.is_ok() {
+                        return Poll::Ready(Some(Guard { lock }));
+                    }
+                }
+                // Register before looking at the state again, so that a release in between
+                // is sure to wake us.
+                State::Running if !registered => {
+                    waiter.register(cx.waker());
+                    registered = true;
+                }
+                State::Running => return Poll::Pending,
+            }
+        }
+    }
+}
+
+/// Resolves once the cell is complete.
+struct Wait<'a, R, T>
+where
+    R: RawOnce,
+{
+    lock: &'a AsyncOnceLock<R, T>,
+    waiter: Waiter<'a>,
+}
+
+impl<R, T> Future for Wait<'_, R, T>
+where
+    R: RawOnce,
+{
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        let lock = self.lock;
+        if lock.state.load(Ordering::Acquire) == State::Complete {
+            return Poll::Ready(());
+        }
+
+        let waiter = unsafe { self.as_ref().map_unchecked(|wait| &wait.waiter) };
+        waiter.register(cx.waker());
+        if lock.state.load(Ordering::Acquire) == State::Complete {
+            Poll::Ready(())
+        } else {
+            Poll::Pending
+        }
+    }
+}
+
+/// Poisons the cell and wakes every waiting task unless the initializer completes.
+///
+/// This covers errors and panics as well as the initializing future being dropped.
+struct Guard<'a, R, T>
+where
+    R: RawOnce,
+{
+    lock: &'a AsyncOnceLock<R, T>,
+}
+
+impl<R, T> Guard<'_, R, T>
+where
+    R: RawOnce,
+{
+    fn complete(self, value: T) {
+        let lock = self.lock;
+        mem::forget(self);
+
+        // We hold the running state, so nobody else is setting the cell.
+        let _ = lock.cell.set(value);
+        lock.state.store(State::Complete, Ordering::Release);
+        lock.wakers.wake_all();
+    }
+}
+
+impl<R, T> Drop for Guard<'_, R, T>
+where
+    R: RawOnce,
+{
+    fn drop(&mut self) {
+        self.lock.state.store(State::Poisoned, Ordering::Release);
+        self.lock.wakers.wake_all();
+    }
+}
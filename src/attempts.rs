@@ -0,0 +1,154 @@
+use core::cell::UnsafeCell;
+use libstd::{
+    any::Any,
+    panic::{self, AssertUnwindSafe},
+    string::String,
+};
+
+use crate::{
+    Deadline, OnceState, RawOnce, RawOnceNonBlocking, RawOnceTimed, RawOnceWait, Timeout,
+    WouldBlock,
+};
+
+/// Wraps a [`RawOnce`] to keep count of the initializers run on it and remember why the
+/// first of them panicked, reported through [`OnceState::attempt`] and
+/// [`OnceState::panic_message`].
+///
+/// Initializers that a [`PoisonPolicy`](crate::PoisonPolicy) refuses to run are not
+/// counted. Initialization through [`RawOnceSplit`](crate::RawOnceSplit) cannot be
+/// watched from start to end, so the wrapper does not offer it.
+pub struct RawAttemptsOnce<R> {
+    raw: R,
+    count: UnsafeCell<u32>,
+    panic_message: UnsafeCell<Option<String>>,
+}
+
+impl<R> RawAttemptsOnce<R> {
+    /// Runs `f` as the next attempt, handing it `state` along with what is known about
+    /// earlier attempts.
+    ///
+    /// # Safety
+    /// Must only be called by the initializer of the wrapped once.
+    unsafe fn run<F, T>(&self, state: &OnceState, f: F) -> T
+    where
+        F: FnOnce(&OnceState) -> T,
+    {
+        let count = unsafe { &mut *self.count.get() };
+        let first_panic = unsafe { &mut *self.panic_message.get() };
+
+        let mut attempt = if state.is_poisoned() {
+            OnceState::poisoned()
+        } else {
+            OnceState::new()
+        };
+        attempt
+            .set_attempt(count.saturating_add(1))
+            .set_panic_message(first_panic.clone());
+
+        let res = panic::catch_unwind(AssertUnwindSafe(|| f(&attempt)));
+        if attempt.is_skipped() {
+            return res.unwrap_or_else(|payload| panic::resume_unwind(payload));
+        }
+
+        *count = count.saturating_add(1);
+        res.unwrap_or_else(|payload| {
+            // Later panics are usually caused by the first one, so keep that.
+            if first_panic.is_none() {
+                *first_panic = Some(payload_message(&*payload));
+            }
+            panic::resume_unwind(payload)
+        })
+    }
+}
+
+unsafe impl<R> RawOnce for RawAttemptsOnce<R>
+where
+    R: RawOnce,
+{
+    #[allow(clippy::declare_interior_mutable_const)]
+    const COMPLETE: Self = Self {
+        raw: R::COMPLETE,
+        count: UnsafeCell::new(0),
+        panic_message: UnsafeCell::new(None),
+    };
+    #[allow(clippy::declare_interior_mutable_const)]
+    const INCOMPLETE: Self = Self {
+        raw: R::INCOMPLETE,
+        count: UnsafeCell::new(0),
+        panic_message: UnsafeCell::new(None),
+    };
+
+    #[inline]
+    fn is_completed(&self) -> bool {
+        self.raw.is_completed()
+    }
+
+    fn is_poisoned(&self) -> bool {
+        self.raw.is_poisoned()
+    }
+
+    fn is_reentrant(&self) -> bool {
+        self.raw.is_reentrant()
+    }
+
+    fn call_dyn(&self, f: &mut dyn FnMut(&OnceState) -> bool) {
+        self.raw
+            .call_dyn(&mut |state| unsafe { self.run(state, &mut *f) })
+    }
+}
+
+unsafe impl<R> RawOnceWait for RawAttemptsOnce<R>
+where
+    R: RawOnceWait,
+{
+    fn wait(&self, ignore_poisoning: bool) -> bool {
+        self.raw.wait(ignore_poisoning)
+    }
+}
+
+unsafe impl<R> RawOnceTimed for RawAttemptsOnce<R>
+where
+    R: RawOnceTimed,
+{
+    fn call_until<F, E, D>(&self, f: F, deadline: &D) -> Result<Result<(), E>, Timeout>
+    where
+        F: FnOnce(&OnceState) -> Result<(), E>,
+        D: Deadline,
+    {
+        self.raw
+            .call_until(|state| unsafe { self.run(state, f) }, deadline)
+    }
+
+    fn wait_until<D>(&self, ignore_poisoning: bool, deadline: &D) -> Result<bool, Timeout>
+    where
+        D: Deadline,
+    {
+        self.raw.wait_until(ignore_poisoning, deadline)
+    }
+}
+
+unsafe impl<R> RawOnceNonBlocking for RawAttemptsOnce<R>
+where
+    R: RawOnceNonBlocking,
+{
+    fn try_call<F, E>(&self, f: F) -> Result<Result<(), E>, WouldBlock>
+    where
+        F: FnOnce(&OnceState) -> Result<(), E>,
+    {
+        self.raw.try_call(|state| unsafe { self.run(state, f) })
+    }
+}
+
+// Only the initializer of the wrapped once touches the attempts, and the once's state
+// orders it with the initializers before and after it.
+unsafe impl<R> Sync for RawAttemptsOnce<R> where R: Sync {}
+
+fn payload_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        String::from(*message)
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        String::from("Box<dyn Any>")
+    }
+}
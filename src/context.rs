@@ -0,0 +1,178 @@
+//! Identifying the execution context that is running an initializer.
+//!
+//! Backends record which context moved a once into the running state, so that a context
+//! calling back into a once from within its own initializer is reported instead of
+//! waiting on itself forever.
+
+use core::{
+    marker::PhantomData,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+/// An execution context, such as a thread or an interrupt priority level.
+///
+/// # Safety
+/// [`Context::current`] must never return the same nonzero id to two contexts that can be
+/// running at the same time. Reentrancy is misreported otherwise.
+pub unsafe trait Context {
+    /// Where a once records the id of the context running its initializer.
+    type Storage: OwnerStorage;
+
+    /// An id for the context the caller is running in, or `0` if it is not known.
+    fn current() -> usize;
+}
+
+/// Storage for the id of the context running a once's initializer.
+///
+/// Contexts that can never be identified use `()`, so that backends generic over the
+/// context spend no space on it.
+pub trait OwnerStorage {
+    /// Storage holding the id `0`.
+    const EMPTY: Self;
+
+    /// The id last stored, or `0`.
+    fn load(&self) -> usize;
+
+    /// Records `id`, which is only ever compared against by the context that stored it.
+    fn store(&self, id: usize);
+}
+
+impl OwnerStorage for AtomicUsize {
+    #[allow(clippy::declare_interior_mutable_const)]
+    const EMPTY: Self = AtomicUsize::new(0);
+
+    #[inline]
+    fn load(&self) -> usize {
+        // Only the owner itself ever compares against the id it stored, so relaxed is
+        // fine.
+        self.load(Ordering::Relaxed)
+    }
+
+    #[inline]
+    fn store(&self, id: usize) {
+        self.store(id, Ordering::Relaxed);
+    }
+}
+
+impl OwnerStorage for () {
+    const EMPTY: Self = ();
+
+    #[inline]
+    fn load(&self) -> usize {
+        0
+    }
+
+    #[inline]
+    fn store(&self, _id: usize) {}
+}
+
+/// A context that cannot be identified.
+///
+/// Reentrancy is never detected, so a once that is initialized from within its own
+/// initializer waits on itself forever. Only use it where that cannot happen, or where
+/// nothing better is available to identify contexts.
+pub struct Unidentified;
+
+unsafe impl Context for Unidentified {
+    type Storage = ();
+
+    #[inline]
+    fn current() -> usize {
+        0
+    }
+}
+
+/// A thread, identified by the address of a thread local.
+#[cfg(any(feature = "std", test))]
+#[cfg_attr(not(feature = "std"), allow(dead_code))]
+pub struct StdThread;
+
+#[cfg(any(feature = "std", test))]
+unsafe impl Context for StdThread {
+    type Storage = AtomicUsize;
+
+    #[inline]
+    fn current() -> usize {
+        libstd::thread_local! {
+            static ID: u8 = const { 0 };
+        }
+
+        ID.with(|id| id as *const u8 as usize)
+    }
+}
+
+/// The context currently running a once's initializer.
+#[cfg_attr(
+    not(any(
+        feature = "std",
+        feature = "spin",
+        feature = "critical-section",
+        feature = "linux-futex"
+    )),
+    allow(dead_code)
+)]
+pub(crate) struct Owner<C>
+where
+    C: Context,
+{
+    id: C::Storage,
+    _context: PhantomData<fn() -> C>,
+}
+
+#[cfg_attr(
+    not(any(
+        feature = "std",
+        feature = "spin",
+        feature = "critical-section",
+        feature = "linux-futex"
+    )),
+    allow(dead_code)
+)]
+impl<C> Owner<C>
+where
+    C: Context,
+{
+    pub const fn new() -> Self {
+        Self {
+            id: C::Storage::EMPTY,
+            _context: PhantomData,
+        }
+    }
+
+    /// Records the current context as the owner until the returned guard is dropped.
+    ///
+    /// Must only be called by the context that just moved the once into the running state.
+    pub fn enter(&self) -> Entered<'_, C> {
+        self.id.store(C::current());
+        Entered { id: &self.id }
+    }
+
+    /// Clears the owner recorded by an [`Owner::enter`] whose guard was forgotten, for
+    /// onces that stay running after the call that acquired them returns.
+    pub fn leave(&self) {
+        self.id.store(0);
+    }
+
+    /// Check if the current context is running the initializer.
+    pub fn is_current(&self) -> bool {
+        let current = C::current();
+        current != 0 && self.id.load() == current
+    }
+}
+
+/// Clears the owner once the initializer returns or unwinds.
+pub(crate) struct Entered<'a, C>
+where
+    C: Context,
+{
+    id: &'a C::Storage,
+}
+
+impl<C> Drop for Entered<'_, C>
+where
+    C: Context,
+{
+    fn drop(&mut self) {
+        self.id.store(0);
+    }
+}
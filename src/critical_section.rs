@@ -0,0 +1,358 @@
+//! A [`RawOnce`] implementation built on the [`critical_section`] crate.
+//!
+//! The initializer runs inside a critical section, so other contexts can never observe
+//! the once while it is running. This makes the backend suitable for single-core
+//! embedded targets, at the cost of keeping interrupts masked for the whole
+//! initialization.
+//!
+//! Entering a critical section cannot time out, and with a lock-based implementation it
+//! waits for whoever holds the lock, so [`RawCsOnce`] has neither deadline-bounded nor
+//! non-blocking operations.
+//!
+//! [`RawSplitCsOnce`] only enters critical sections to move between states and runs the
+//! initializer with interrupts enabled. Contexts that find it running spin until it is
+//! done.
+
+use core::{mem, sync::atomic::Ordering};
+
+use crate::{
+    context::{Context, Owner},
+    into_ok,
+    limit::{Limit, NonBlocking, Unlimited, Until},
+    state::{AtomicState, State},
+    Deadline, OnceState, RawOnce, RawOnceNonBlocking, RawOnceSplit, RawOnceTimed, RawOnceWait,
+    Timeout, WouldBlock,
+};
+
+pub type Once<P = crate::PanicOnPoison> = crate::Once<RawCsOnce, P>;
+pub type OnceLock<T, P = crate::IgnorePoison> = crate::OnceLock<RawCsOnce, T, P>;
+pub type OnceResult<T, E> = crate::OnceResult<RawCsOnce, T, E>;
+pub type LazyLock<T, F = fn() -> T> = crate::LazyLock<RawCsOnce, T, F>;
+pub type TryLazyLock<T, E, F, P = crate::RetryOnError> = crate::TryLazyLock<RawCsOnce, T, E, F, P>;
+pub type AsyncOnceLock<T> = crate::AsyncOnceLock<RawCsOnce, T>;
+pub type AsyncLazy<T, F> = crate::AsyncLazy<RawCsOnce, T, F>;
+
+pub type SplitOnce<C, P = crate::PanicOnPoison> = crate::Once<RawSplitCsOnce<C>, P>;
+pub type SplitOnceLock<C, T, P = crate::IgnorePoison> = crate::OnceLock<RawSplitCsOnce<C>, T, P>;
+pub type SplitOnceResult<C, T, E> = crate::OnceResult<RawSplitCsOnce<C>, T, E>;
+pub type SplitLazyLock<C, T, F = fn() -> T> = crate::LazyLock<RawSplitCsOnce<C>, T, F>;
+pub type SplitTryLazyLock<C, T, E, F, P = crate::RetryOnError> =
+    crate::TryLazyLock<RawSplitCsOnce<C>, T, E, F, P>;
+
+pub struct RawCsOnce {
+    state: AtomicState,
+}
+
+impl RawCsOnce {
+    /// Runs `f` inside a critical section, unless the once is already running.
+    ///
+    /// The critical section is held for the whole initialization, so the once can only
+    /// be found running from within its own initializer.
+    fn call_if_idle<F, E>(&self, f: F) -> Result<Result<(), E>, WouldBlock>
+    where
+        F: FnOnce(&OnceState) -> Result<(), E>,
+    {
+        critical_section::with(|_cs| {
+            // Acquire might not be entirely necessary, but the perf impact will be
+            // minimal.
+            let state = self.state.load(Ordering::Acquire);
+
+            let once_state = match state {
+                State::Running => return Err(WouldBlock),
+                State::Complete => return Ok(Ok(())),
+                State::Poisoned => OnceState::poisoned(),
+                State::Incomplete => OnceState::new(),
+            };
+
+            // The critical section guarantees no other threads will be writing to the state,
+            // so relaxed is fine.
+            self.state.store(State::Running, Ordering::Relaxed);
+
+            let guard = Guard { state: &self.state };
+
+            if let Err(err) = f(&once_state) {
+                return Ok(Err(err));
+            }
+            mem::forget(guard);
+            self.state.store(State::Complete, Ordering::Release);
+            Ok(Ok(()))
+        })
+    }
+}
+
+unsafe impl RawOnce for RawCsOnce {
+    #[allow(clippy::declare_interior_mutable_const)]
+    const COMPLETE: Self = Self {
+        state: AtomicState::new(State::Complete),
+    };
+    #[allow(clippy::declare_interior_mutable_const)]
+    const INCOMPLETE: Self = Self {
+        state: AtomicState::new(State::Incomplete),
+    };
+
+    #[inline]
+    fn is_completed(&self) -> bool {
+        self.state.load(Ordering::Acquire) == State::Complete
+    }
+
+    fn is_poisoned(&self) -> bool {
+        self.state.load(Ordering::Acquire) == State::Poisoned
+    }
+
+    /// The critical section is held for the whole initialization, so finding the once
+    /// running from inside a critical section means we are inside its initializer.
+    fn is_reentrant(&self) -> bool {
+        critical_section::with(|_cs| self.state.load(Ordering::Relaxed) == State::Running)
+    }
+
+    fn call_dyn(&self, f: &mut dyn FnMut(&OnceState) -> bool) {
+        if self
+            .call_if_idle(|state| f(state).then_some(()).ok_or(()))
+            .is_err()
+        {
+            panic!("reentrant once call");
+        }
+    }
+}
+
+unsafe impl RawOnceWait for RawCsOnce {
+    fn wait(&self, ignore_poisoning: bool) -> bool {
+        into_ok(self.state.spin_wait(ignore_poisoning, &Unlimited))
+    }
+}
+
+/// Poisons the once if the initializer panics or fails.
+struct Guard<'a> {
+    state: &'a AtomicState,
+}
+
+impl<'a> Drop for Guard<'a> {
+    fn drop(&mut self) {
+        self.state.store(State::Poisoned, Ordering::Release);
+    }
+}
+
+/// A critical-section once that runs the initializer outside of the critical section.
+///
+/// Only the transitions into and out of the running state happen inside a critical
+/// section, so a slow initializer does not delay interrupts. A context that finds the once
+/// running spins until it finishes. On a single core this means that an interrupt handler
+/// which touches a once that the code it interrupted is initializing will never return,
+/// unless `C` tells the two contexts apart so that the reentrant call is reported instead.
+/// With [`Unidentified`](crate::Unidentified), it spins until its deadline, or forever
+/// without one.
+pub struct RawSplitCsOnce<C>
+where
+    C: Context,
+{
+    state: AtomicState,
+    owner: Owner<C>,
+}
+
+impl<C> RawSplitCsOnce<C>
+where
+    C: Context,
+{
+    fn acquire<L>(&self, limit: &L) -> Result<Option<OnceState>, L::Error>
+    where
+        L: Limit,
+    {
+        loop {
+            let step = critical_section::with(|_cs| {
+                let once_state = match self.state.load(Ordering::Acquire) {
+                    State::Running => return Step::Wait,
+                    State::Complete => return Step::Complete,
+                    State::Poisoned => OnceState::poisoned(),
+                    State::Incomplete => OnceState::new(),
+                };
+
+                // The critical section guarantees no other context is writing to the
+                // state, so relaxed is fine.
+                self.state.store(State::Running, Ordering::Relaxed);
+                Step::Acquired(once_state)
+            });
+
+            match step {
+                Step::Acquired(once_state) => return Ok(Some(once_state)),
+                Step::Complete => return Ok(None),
+                Step::Wait => self.state.spin_while_running(limit)?,
+            }
+        }
+    }
+
+    fn call_limited<F, E, L>(&self, f: F, limit: &L) -> Result<Result<(), E>, L::Error>
+    where
+        F: FnOnce(&OnceState) -> Result<(), E>,
+        L: Limit,
+    {
+        let once_state = match self.acquire(limit)? {
+            Some(once_state) => once_state,
+            None => return Ok(Ok(())),
+        };
+
+        let guard = SplitGuard { state: &self.state };
+
+        let entered = self.owner.enter();
+        let res = f(&once_state);
+        mem::drop(entered);
+
+        if let Err(err) = res {
+            return Ok(Err(err));
+        }
+        self.finish_init(guard);
+        Ok(Ok(()))
+    }
+
+    fn finish_init(&self, guard: SplitGuard<'_>) {
+        mem::forget(guard);
+        critical_section::with(|_cs| self.state.store(State::Complete, Ordering::Release));
+    }
+}
+
+unsafe impl<C> RawOnce for RawSplitCsOnce<C>
+where
+    C: Context,
+{
+    #[allow(clippy::declare_interior_mutable_const)]
+    const COMPLETE: Self = Self {
+        state: AtomicState::new(State::Complete),
+        owner: Owner::new(),
+    };
+    #[allow(clippy::declare_interior_mutable_const)]
+    const INCOMPLETE: Self = Self {
+        state: AtomicState::new(State::Incomplete),
+        owner: Owner::new(),
+    };
+
+    #[inline]
+    fn is_completed(&self) -> bool {
+        self.state.load(Ordering::Acquire) == State::Complete
+    }
+
+    fn is_poisoned(&self) -> bool {
+        self.state.load(Ordering::Acquire) == State::Poisoned
+    }
+
+    fn is_reentrant(&self) -> bool {
+        self.owner.is_current()
+    }
+
+    #[cold]
+    fn call_dyn(&self, f: &mut dyn FnMut(&OnceState) -> bool) {
+        // A failed initializer poisons the once, there is nothing else to report.
+        let _ = into_ok(self.call_limited(|state| f(state).then_some(()).ok_or(()), &Unlimited));
+    }
+}
+
+unsafe impl<C> RawOnceWait for RawSplitCsOnce<C>
+where
+    C: Context,
+{
+    fn wait(&self, ignore_poisoning: bool) -> bool {
+        into_ok(self.state.spin_wait(ignore_poisoning, &Unlimited))
+    }
+}
+
+unsafe impl<C> RawOnceTimed for RawSplitCsOnce<C>
+where
+    C: Context,
+{
+    #[cold]
+    fn call_until<F, E, D>(&self, f: F, deadline: &D) -> Result<Result<(), E>, Timeout>
+    where
+        F: FnOnce(&OnceState) -> Result<(), E>,
+        D: Deadline,
+    {
+        self.call_limited(f, &Until(deadline))
+    }
+
+    fn wait_until<D>(&self, ignore_poisoning: bool, deadline: &D) -> Result<bool, Timeout>
+    where
+        D: Deadline,
+    {
+        self.state.spin_wait(ignore_poisoning, &Until(deadline))
+    }
+}
+
+unsafe impl<C> RawOnceNonBlocking for RawSplitCsOnce<C>
+where
+    C: Context,
+{
+    #[cold]
+    fn try_call<F, E>(&self, f: F) -> Result<Result<(), E>, WouldBlock>
+    where
+        F: FnOnce(&OnceState) -> Result<(), E>,
+    {
+        self.call_limited(f, &NonBlocking)
+    }
+}
+
+unsafe impl<C> RawOnceSplit for RawSplitCsOnce<C>
+where
+    C: Context,
+{
+    fn acquire(&self) -> Option<OnceState> {
+        let once_state = into_ok(self.acquire(&Unlimited))?;
+        mem::forget(self.owner.enter());
+        Some(once_state)
+    }
+
+    unsafe fn release(&self, complete: bool) {
+        self.owner.leave();
+        let guard = SplitGuard { state: &self.state };
+        if complete {
+            self.finish_init(guard);
+        }
+    }
+}
+
+enum Step {
+    Acquired(OnceState),
+    Complete,
+    Wait,
+}
+
+/// Poisons a [`RawSplitCsOnce`] if the initializer panics or fails.
+struct SplitGuard<'a> {
+    state: &'a AtomicState,
+}
+
+impl<'a> Drop for SplitGuard<'a> {
+    fn drop(&mut self) {
+        critical_section::with(|_cs| self.state.store(State::Poisoned, Ordering::Release));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RawCsOnce;
+    use crate::testing;
+
+    testing::backend_tests!(RawCsOnce, [wait]);
+
+    mod split {
+        use crate::{context::StdThread, critical_section::RawSplitCsOnce, testing, Unidentified};
+
+        testing::backend_tests!(
+            RawSplitCsOnce<Unidentified>,
+            identified = RawSplitCsOnce<StdThread>,
+            [split, wait, timed, nonblocking]
+        );
+
+        #[test]
+        fn unidentified_reentrancy_spins() {
+            testing::unidentified_reentrancy_spins::<RawSplitCsOnce<Unidentified>>();
+        }
+
+        #[test]
+        fn runs_init_outside_critical_section() {
+            let cell = crate::critical_section::SplitOnceLock::<Unidentified, _>::new();
+            let value = cell.get_or_init(|| {
+                // With the std implementation, a critical section is a global lock, so this
+                // would deadlock if the initializer ran inside one on another thread.
+                libstd::thread::scope(|s| s.spawn(|| critical_section::with(|_| 7)).join().unwrap())
+            });
+            assert_eq!(*value, 7);
+        }
+    }
+}
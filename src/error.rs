@@ -0,0 +1,110 @@
+use core::{convert::Infallible, fmt};
+
+use crate::{into_ok, OnceState};
+
+/// The once was being initialized by another caller, and the operation would have had to
+/// wait for it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WouldBlock;
+
+impl fmt::Display for WouldBlock {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("once is being initialized by another caller")
+    }
+}
+
+#[cfg(feature = "std")]
+impl libstd::error::Error for WouldBlock {}
+
+/// Why initializing a once did not succeed.
+///
+/// `E` defaults to [`Infallible`] for operations whose initializer cannot fail.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OnceError<E = Infallible> {
+    /// The once was initialized from within its own initializer.
+    ///
+    /// Waiting would never finish, so the initializer is not run.
+    Reentrant,
+    /// An earlier initializer panicked, and the once cannot be initialized again.
+    Poisoned,
+    /// The initializer failed.
+    Failed(E),
+}
+
+impl<E> fmt::Display for OnceError<E>
+where
+    E: fmt::Display,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Reentrant => f.write_str("once initialized from within its own initializer"),
+            Self::Poisoned => f.write_str("once poisoned by an earlier initializer"),
+            Self::Failed(err) => err.fmt(f),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<E> libstd::error::Error for OnceError<E>
+where
+    E: libstd::error::Error + 'static,
+{
+    fn source(&self) -> Option<&(dyn libstd::error::Error + 'static)> {
+        match self {
+            Self::Reentrant | Self::Poisoned => None,
+            Self::Failed(err) => Some(err),
+        }
+    }
+}
+
+/// Unwraps the result of an infallible operation, panicking if it was reentered or
+/// poisoned.
+#[track_caller]
+pub(crate) fn unwrap_once<T>(res: Result<T, OnceError>) -> T {
+    into_ok(unwrap_failed(res))
+}
+
+/// Leaves only the failure of an operation, panicking if it was reentered or poisoned.
+#[track_caller]
+pub(crate) fn unwrap_failed<T, E>(res: Result<T, OnceError<E>>) -> Result<T, E> {
+    match res {
+        Ok(value) => Ok(value),
+        Err(OnceError::Reentrant) => reentrant(),
+        Err(OnceError::Poisoned) => panic!("Once poisoned"),
+        Err(OnceError::Failed(err)) => Err(err),
+    }
+}
+
+/// Merges the errors of an initializer that reports [`OnceError`]s itself into those of
+/// the once running it.
+pub(crate) fn flatten<T, E>(res: Result<T, OnceError<OnceError<E>>>) -> Result<T, OnceError<E>> {
+    res.map_err(|err| match err {
+        OnceError::Reentrant => OnceError::Reentrant,
+        OnceError::Poisoned => OnceError::Poisoned,
+        OnceError::Failed(err) => err,
+    })
+}
+
+/// Panics on behalf of an operation that found the once poisoned and cannot report it,
+/// including why the once was poisoned if the backend knows.
+#[cold]
+#[track_caller]
+pub(crate) fn poisoned(state: &OnceState) -> ! {
+    #[cfg(feature = "std")]
+    if let Some(message) = state.panic_message() {
+        panic!(
+            "Once poisoned on attempt {}, an earlier initializer panicked with: {message}",
+            state.attempt()
+        );
+    }
+    #[cfg(not(feature = "std"))]
+    let _ = state;
+    panic!("Once poisoned")
+}
+
+/// Panics on behalf of an infallible operation that found itself reentered.
+#[cold]
+#[track_caller]
+pub(crate) fn reentrant() -> ! {
+    panic!("once initialized from within its own initializer")
+}
@@ -1,82 +1,134 @@
-// use core::{cell::UnsafeCell, convert::Infallible};
+use core::{cell::UnsafeCell, convert::Infallible, fmt};
 
-// use crate::RawOnce;
+use crate::{error::reentrant, once::call_erased, RawOnce};
 
-// pub struct ExclusiveCell<R, T> {
-//     cell: UnsafeCell<Option<T>>,
-//     once: R,
-// }
+/// A cell that hands out a mutable reference to its value exactly once.
+///
+/// Only the call that initializes the cell gets the reference, so a `static` cell can be
+/// used to safely obtain a `&'static mut T`. Every later call fails with
+/// [`TryInitError::AlreadyInitialized`]. If the initializer fails or panics, the cell is
+/// left uninitialized and the next call may try again.
+pub struct ExclusiveCell<R, T> {
+    cell: UnsafeCell<Option<T>>,
+    once: R,
+}
 
-// #[derive(Debug)]
-// pub enum TryInitError<E> {
-//     AlreadyInitialized,
-//     Err(E),
-// }
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TryInitError<E> {
+    /// The cell was initialized by an earlier call, which received the reference.
+    AlreadyInitialized,
+    /// The cell was initialized from within its own initializer.
+    Reentrant,
+    /// The initializer failed.
+    Err(E),
+}
 
-// #[derive(Debug)]
-// pub enum TryInitError<E> {
-//     AlreadyInitialized,
-//     Err(E),
-// }
+impl<E> fmt::Display for TryInitError<E>
+where
+    E: fmt::Display,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::AlreadyInitialized => f.write_str("cell was already initialized"),
+            Self::Reentrant => f.write_str("cell initialized from within its own initializer"),
+            Self::Err(err) => err.fmt(f),
+        }
+    }
+}
 
-// impl<R, T> ExclusiveCell<R, T>
-// where
-//     R: RawOnce,
-// {
-//     pub const fn new() -> Self {
-//         Self {
-//             cell: UnsafeCell::new(None),
-//             once: R::INIT,
-//         }
-//     }
+#[cfg(feature = "std")]
+impl<E> libstd::error::Error for TryInitError<E>
+where
+    E: libstd::error::Error + 'static,
+{
+    fn source(&self) -> Option<&(dyn libstd::error::Error + 'static)> {
+        match self {
+            Self::AlreadyInitialized | Self::Reentrant => None,
+            Self::Err(err) => Some(err),
+        }
+    }
+}
 
-//     pub fn init<F>(&self, f: F) -> Option<&mut T>
-//     where
-//         F: FnOnce() -> T,
-//     {
-//         self.try_init(|| Ok::<_, Infallible>(f())).ok()
-//     }
+impl<R, T> ExclusiveCell<R, T>
+where
+    R: RawOnce,
+{
+    pub const fn new() -> Self {
+        Self {
+            cell: UnsafeCell::new(None),
+            once: R::INCOMPLETE,
+        }
+    }
 
-//     pub fn try_init<F, E>(&self, f: F) -> Result<&mut T, TryInitError<E>>
-//     where
-//         F: FnOnce() -> Result<T, E>,
-//     {
-//         let mut f = Some(f);
-//         let mut error = None;
+    /// Initialize the cell, returning the only reference to its value.
+    ///
+    /// Returns `None` without calling `f` if the cell has already been initialized.
+    #[allow(clippy::mut_from_ref)]
+    pub fn init<F>(&self, f: F) -> Option<&mut T>
+    where
+        F: FnOnce() -> T,
+    {
+        match self.try_init(|| Ok::<_, Infallible>(f())) {
+            Ok(value) => Some(value),
+            Err(TryInitError::AlreadyInitialized) => None,
+            Err(TryInitError::Reentrant) => reentrant(),
+            Err(TryInitError::Err(err)) => match err {},
+        }
+    }
 
-//         self.once.call(&mut |_| {
-//             let f = unsafe { f.take().unwrap_unchecked() };
+    /// Initialize the cell with a fallible initializer, returning the only reference to its
+    /// value.
+    #[allow(clippy::mut_from_ref)]
+    pub fn try_init<F, E>(&self, f: F) -> Result<&mut T, TryInitError<E>>
+    where
+        F: FnOnce() -> Result<T, E>,
+    {
+        if self.once.is_completed() {
+            return Err(TryInitError::AlreadyInitialized);
+        }
+        if self.once.is_reentrant() {
+            return Err(TryInitError::Reentrant);
+        }
 
-//             match f() {
-//                 Ok(value) => {
-//                     unsafe {
-//                         *self.cell.get() = Some(value);
-//                     }
-//                     true
-//                 }
-//                 Err(err) => {
-//                     error = Some(err);
-//                     false
-//                 }
-//             }
-//         });
+        let mut ran = false;
+        call_erased(&self.once, |_| {
+            ran = true;
+            let value = f()?;
+            unsafe {
+                *self.cell.get() = Some(value);
+            }
+            Ok(())
+        })
+        .map_err(TryInitError::Err)?;
 
-//         if f.is_some() {
-//             Err(TryInitError::AlreadyInitialized)
-//         } else if let Some(err) = error {
-//             Err(TryInitError::Err(err))
-//         } else {
-//             unsafe {
-//                 let value = &mut *self.cell.get();
-//                 Ok(value.as_mut().unwrap_unchecked())
-//             }
-//         }
-//     }
-// }
+        // Someone else completed the once while we were waiting, and got the reference.
+        if !ran {
+            return Err(TryInitError::AlreadyInitialized);
+        }
 
-// unsafe impl<R, T> Sync for ExclusiveCell<R, T>
-// where
-//     R: Sync,
-//     T: Send,
-// {
-// }
+        // We ran the initializer that completed the once, so no one else ever sees the
+        // value.
+        unsafe { Ok((*self.cell.get()).as_mut().unwrap_unchecked()) }
+    }
+
+    /// Check if the cell has been initialized, and its reference handed out.
+    pub fn is_initialized(&self) -> bool {
+        self.once.is_completed()
+    }
+}
+
+impl<R, T> Default for ExclusiveCell<R, T>
+where
+    R: RawOnce,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+unsafe impl<R, T> Sync for ExclusiveCell<R, T>
+where
+    R: Sync,
+    T: Send,
+{
+}
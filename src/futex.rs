@@ -0,0 +1,391 @@
+//! A [`RawOnce`] implementation that sleeps on a Linux futex.
+//!
+//! The once is a `u32` state and the id of the thread running the initializer. Waiting
+//! and waking go straight to the kernel through the `futex` system call, so the backend
+//! needs neither `std` nor any other crate and can be used from `no_std` Linux binaries.
+//!
+//! The system calls are made by hand, so the module is only available on `x86_64`,
+//! `aarch64` and `riscv64`. On other architectures, including every 32-bit one, enabling
+//! the `linux-futex` feature leaves it out.
+
+use core::{
+    mem,
+    sync::atomic::{AtomicU32, AtomicUsize, Ordering},
+};
+
+use crate::{
+    context::{Context, Owner},
+    into_ok,
+    limit::{Limit, NonBlocking, Unlimited, Until},
+    Deadline, OnceState, RawOnce, RawOnceNonBlocking, RawOnceSplit, RawOnceTimed, RawOnceWait,
+    Timeout, WouldBlock,
+};
+
+pub type Once<P = crate::PanicOnPoison> = crate::Once<RawFutexOnce, P>;
+pub type OnceLock<T, P = crate::IgnorePoison> = crate::OnceLock<RawFutexOnce, T, P>;
+pub type OnceResult<T, E> = crate::OnceResult<RawFutexOnce, T, E>;
+pub type LazyLock<T, F = fn() -> T> = crate::LazyLock<RawFutexOnce, T, F>;
+pub type TryLazyLock<T, E, F, P = crate::RetryOnError> =
+    crate::TryLazyLock<RawFutexOnce, T, E, F, P>;
+pub type AsyncOnceLock<T> = crate::AsyncOnceLock<RawFutexOnce, T>;
+pub type AsyncLazy<T, F> = crate::AsyncLazy<RawFutexOnce, T, F>;
+
+// The lower two bits hold one of four states. `QUEUED` is set on top of them once a thread
+// is (about to be) asleep on the futex, so that releasing the once only makes a system
+// call when somebody actually needs waking.
+const INCOMPLETE: u32 = 0x0;
+const POISONED: u32 = 0x1;
+const RUNNING: u32 = 0x2;
+const COMPLETE: u32 = 0x3;
+const STATE_MASK: u32 = 0x3;
+const QUEUED: u32 = 0x4;
+
+pub struct RawFutexOnce {
+    state: AtomicU32,
+    owner: Owner<LinuxThread>,
+}
+
+impl RawFutexOnce {
+    #[inline(never)]
+    fn acquire<L>(&self, limit: &L) -> Result<Option<OnceState>, L::Error>
+    where
+        L: Limit,
+    {
+        let mut state = self.state.load(Ordering::Acquire);
+
+        loop {
+            match state & STATE_MASK {
+                COMPLETE => return Ok(None),
+                INCOMPLETE | POISONED => {
+                    let once_state = if state & STATE_MASK == POISONED {
+                        OnceState::poisoned()
+                    } else {
+                        OnceState::new()
+                    };
+
+                    // Keep the queued bit so the waiters are woken when we are done.
+                    let exchange = self.state.compare_exchange_weak(
+                        state,
+                        (state & QUEUED) | RUNNING,
+                        Ordering::Acquire,
+                        Ordering::Acquire,
+                    );
+                    match exchange {
+                        Ok(_) => return Ok(Some(once_state)),
+                        Err(new) => state = new,
+                    }
+                }
+                _ => state = self.sleep(state, limit)?,
+            }
+        }
+    }
+
+    /// Marks the once as queued and sleeps while it stays in `state`.
+    ///
+    /// Returns the state observed afterwards.
+    fn sleep<L>(&self, state: u32, limit: &L) -> Result<u32, L::Error>
+    where
+        L: Limit,
+    {
+        let timeout = limit.remaining()?;
+
+        if state & QUEUED == 0 {
+            let exchange = self.state.compare_exchange_weak(
+                state,
+                state | QUEUED,
+                Ordering::Relaxed,
+                Ordering::Acquire,
+            );
+            if let Err(new) = exchange {
+                return Ok(new);
+            }
+        }
+
+        sys::futex_wait(&self.state, state | QUEUED, timeout);
+        Ok(self.state.load(Ordering::Acquire))
+    }
+
+    fn call_limited<F, E, L>(&self, f: F, limit: &L) -> Result<Result<(), E>, L::Error>
+    where
+        F: FnOnce(&OnceState) -> Result<(), E>,
+        L: Limit,
+    {
+        let once_state = match self.acquire(limit)? {
+            Some(once_state) => once_state,
+            None => return Ok(Ok(())),
+        };
+
+        let mut guard = Guard {
+            state: &self.state,
+            new_state: POISONED,
+        };
+
+        let entered = self.owner.enter();
+        let res = f(&once_state);
+        mem::drop(entered);
+
+        if let Err(err) = res {
+            return Ok(Err(err));
+        }
+        guard.new_state = COMPLETE;
+        mem::drop(guard);
+        Ok(Ok(()))
+    }
+
+    fn wait_limited<L>(&self, ignore_poisoning: bool, limit: &L) -> Result<bool, L::Error>
+    where
+        L: Limit,
+    {
+        let mut state = self.state.load(Ordering::Acquire);
+
+        loop {
+            match state & STATE_MASK {
+                COMPLETE => return Ok(true),
+                POISONED if !ignore_poisoning => return Ok(false),
+                _ => state = self.sleep(state, limit)?,
+            }
+        }
+    }
+}
+
+unsafe impl RawOnce for RawFutexOnce {
+    #[allow(clippy::declare_interior_mutable_const)]
+    const COMPLETE: Self = Self {
+        state: AtomicU32::new(COMPLETE),
+        owner: Owner::new(),
+    };
+    #[allow(clippy::declare_interior_mutable_const)]
+    const INCOMPLETE: Self = Self {
+        state: AtomicU32::new(INCOMPLETE),
+        owner: Owner::new(),
+    };
+
+    #[inline]
+    fn is_completed(&self) -> bool {
+        self.state.load(Ordering::Acquire) == COMPLETE
+    }
+
+    fn is_poisoned(&self) -> bool {
+        self.state.load(Ordering::Acquire) & STATE_MASK == POISONED
+    }
+
+    fn is_reentrant(&self) -> bool {
+        self.owner.is_current()
+    }
+
+    #[cold]
+    fn call_dyn(&self, f: &mut dyn FnMut(&OnceState) -> bool) {
+        // A failed initializer poisons the once, there is nothing else to report.
+        let _ = into_ok(self.call_limited(|state| f(state).then_some(()).ok_or(()), &Unlimited));
+    }
+}
+
+unsafe impl RawOnceWait for RawFutexOnce {
+    fn wait(&self, ignore_poisoning: bool) -> bool {
+        into_ok(self.wait_limited(ignore_poisoning, &Unlimited))
+    }
+}
+
+unsafe impl RawOnceTimed for RawFutexOnce {
+    #[cold]
+    fn call_until<F, E, D>(&self, f: F, deadline: &D) -> Result<Result<(), E>, Timeout>
+    where
+        F: FnOnce(&OnceState) -> Result<(), E>,
+        D: Deadline,
+    {
+        self.call_limited(f, &Until(deadline))
+    }
+
+    fn wait_until<D>(&self, ignore_poisoning: bool, deadline: &D) -> Result<bool, Timeout>
+    where
+        D: Deadline,
+    {
+        self.wait_limited(ignore_poisoning, &Until(deadline))
+    }
+}
+
+unsafe impl RawOnceNonBlocking for RawFutexOnce {
+    #[cold]
+    fn try_call<F, E>(&self, f: F) -> Result<Result<(), E>, WouldBlock>
+    where
+        F: FnOnce(&OnceState) -> Result<(), E>,
+    {
+        self.call_limited(f, &NonBlocking)
+    }
+}
+
+unsafe impl RawOnceSplit for RawFutexOnce {
+    fn acquire(&self) -> Option<OnceState> {
+        let once_state = into_ok(self.acquire(&Unlimited))?;
+        mem::forget(self.owner.enter());
+        Some(once_state)
+    }
+
+    unsafe fn release(&self, complete: bool) {
+        self.owner.leave();
+        mem::drop(Guard {
+            state: &self.state,
+            new_state: if complete { COMPLETE } else { POISONED },
+        });
+    }
+}
+
+/// A thread, identified by its kernel thread id.
+///
+/// Needs a system call to find out, but no thread locals, so it also works in `no_std`
+/// binaries.
+pub struct LinuxThread;
+
+unsafe impl Context for LinuxThread {
+    type Storage = AtomicUsize;
+
+    #[inline]
+    fn current() -> usize {
+        sys::gettid()
+    }
+}
+
+/// Publishes the final state and wakes every waiter on drop.
+///
+/// The state is left poisoned unless `new_state` was updated, which covers both a panic
+/// and an error returned by the initializer.
+struct Guard<'a> {
+    state: &'a AtomicU32,
+    new_state: u32,
+}
+
+impl Drop for Guard<'_> {
+    fn drop(&mut self) {
+        let state = self.state.swap(self.new_state, Ordering::AcqRel);
+        if state & QUEUED != 0 {
+            sys::futex_wake_all(self.state);
+        }
+    }
+}
+
+mod sys {
+    use core::{ptr, sync::atomic::AtomicU32, time::Duration};
+
+    const FUTEX_WAIT: usize = 0;
+    const FUTEX_WAKE: usize = 1;
+    const FUTEX_PRIVATE_FLAG: usize = 128;
+
+    #[cfg(target_arch = "x86_64")]
+    const SYS_FUTEX: usize = 202;
+    #[cfg(any(target_arch = "aarch64", target_arch = "riscv64"))]
+    const SYS_FUTEX: usize = 98;
+    #[cfg(target_arch = "x86_64")]
+    const SYS_GETTID: usize = 186;
+    #[cfg(any(target_arch = "aarch64", target_arch = "riscv64"))]
+    const SYS_GETTID: usize = 178;
+
+    #[repr(C)]
+    struct Timespec {
+        tv_sec: i64,
+        tv_nsec: i64,
+    }
+
+    /// Sleeps until woken or `timeout` elapses, as long as `futex` still holds `expected`.
+    ///
+    /// Spurious wakeups are possible, so callers must recheck the state.
+    pub fn futex_wait(futex: &AtomicU32, expected: u32, timeout: Option<Duration>) {
+        // A timeout too long to represent is as good as none at all.
+        let timespec = timeout.and_then(|timeout| {
+            Some(Timespec {
+                tv_sec: i64::try_from(timeout.as_secs()).ok()?,
+                tv_nsec: i64::from(timeout.subsec_nanos()),
+            })
+        });
+        let timespec = timespec
+            .as_ref()
+            .map_or(ptr::null(), |timespec| timespec as *const Timespec);
+
+        unsafe {
+            syscall4(
+                SYS_FUTEX,
+                futex.as_ptr() as usize,
+                FUTEX_WAIT | FUTEX_PRIVATE_FLAG,
+                expected as usize,
+                timespec as usize,
+            );
+        }
+    }
+
+    pub fn futex_wake_all(futex: &AtomicU32) {
+        unsafe {
+            syscall4(
+                SYS_FUTEX,
+                futex.as_ptr() as usize,
+                FUTEX_WAKE | FUTEX_PRIVATE_FLAG,
+                i32::MAX as usize,
+                0,
+            );
+        }
+    }
+
+    /// The calling thread's id, which is never zero.
+    pub fn gettid() -> usize {
+        unsafe { syscall4(SYS_GETTID, 0, 0, 0, 0) as usize }
+    }
+
+    #[cfg(target_arch = "x86_64")]
+    unsafe fn syscall4(n: usize, a1: usize, a2: usize, a3: usize, a4: usize) -> isize {
+        let ret;
+        unsafe {
+            core::arch::asm!(
+                "syscall",
+                inlateout("rax") n => ret,
+                in("rdi") a1,
+                in("rsi") a2,
+                in("rdx") a3,
+                in("r10") a4,
+                lateout("rcx") _,
+                lateout("r11") _,
+                options(nostack),
+            );
+        }
+        ret
+    }
+
+    #[cfg(target_arch = "aarch64")]
+    unsafe fn syscall4(n: usize, a1: usize, a2: usize, a3: usize, a4: usize) -> isize {
+        let ret;
+        unsafe {
+            core::arch::asm!(
+                "svc 0",
+                in("x8") n,
+                inlateout("x0") a1 => ret,
+                in("x1") a2,
+                in("x2") a3,
+                in("x3") a4,
+                options(nostack),
+            );
+        }
+        ret
+    }
+
+    #[cfg(target_arch = "riscv64")]
+    unsafe fn syscall4(n: usize, a1: usize, a2: usize, a3: usize, a4: usize) -> isize {
+        let ret;
+        unsafe {
+            core::arch::asm!(
+                "ecall",
+                in("a7") n,
+                inlateout("a0") a1 => ret,
+                in("a1") a2,
+                in("a2") a3,
+                in("a3") a4,
+                options(nostack),
+            );
+        }
+        ret
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RawFutexOnce;
+    use crate::testing;
+
+    testing::backend_tests!(RawFutexOnce, [split, wait, timed, nonblocking]);
+}
@@ -1,13 +1,30 @@
 use core::{
-    cell::Cell,
+    cell::UnsafeCell,
+    mem::ManuallyDrop,
     ops::{Deref, DerefMut},
+    ptr,
 };
 
-use crate::{OnceLock, RawOnce};
+use crate::{error::reentrant, once::call_erased, OnceError, RawOnce};
 
-pub struct LazyLock<R, T, F = fn() -> T> {
-    cell: OnceLock<R, T>,
-    init: Cell<Option<F>>,
+/// A value that is initialized on first access.
+///
+/// The initializer and the value share one slot, so the lazy is no larger than the once
+/// and the bigger of the two. Like [`OnceLock`](crate::OnceLock), it is therefore dropped
+/// by hand, and must be dropped before anything the value or the initializer borrows from.
+pub struct LazyLock<R, T, F = fn() -> T>
+where
+    R: RawOnce,
+{
+    once: R,
+    data: UnsafeCell<Data<T, F>>,
+}
+
+/// Holds the initializer while the once is incomplete and the value once it is complete.
+/// The initializer is moved out when it starts running, so a poisoned once holds neither.
+union Data<T, F> {
+    value: ManuallyDrop<T>,
+    init: ManuallyDrop<F>,
 }
 
 impl<R, T, F> LazyLock<R, T, F>
@@ -16,10 +33,74 @@ where
 {
     pub const fn new(init: F) -> Self {
         Self {
-            cell: OnceLock::new(),
-            init: Cell::new(Some(init)),
+            once: R::INCOMPLETE,
+            data: UnsafeCell::new(Data {
+                init: ManuallyDrop::new(init),
+            }),
+        }
+    }
+
+    /// The value, if it has been initialized, without forcing it.
+    pub fn get(this: &Self) -> Option<&T> {
+        if this.once.is_completed() {
+            Some(unsafe { &(*this.data.get()).value })
+        } else {
+            None
+        }
+    }
+
+    /// Like [`LazyLock::get`], but mutable.
+    pub fn get_mut(this: &mut Self) -> Option<&mut T> {
+        if this.once.is_completed() {
+            Some(unsafe { &mut this.data.get_mut().value })
+        } else {
+            None
+        }
+    }
+
+    /// Consume the lazy, returning the value if it has been initialized, or the
+    /// initializer if it has not.
+    ///
+    /// # Panics
+    /// Panics if the initializer panicked on an earlier access.
+    pub fn into_inner(this: Self) -> Result<T, F> {
+        match Self::try_into_inner(this) {
+            Ok(value) => Ok(value),
+            Err(OnceError::Failed(init)) => Err(init),
+            Err(_) => panic!("LazyLock instance has previously been poisoned"),
+        }
+    }
+
+    /// Like [`LazyLock::into_inner`], but reports poisoning instead of panicking.
+    ///
+    /// # Errors
+    /// Returns [`OnceError::Failed`] with the initializer if the value has not been
+    /// initialized, and [`OnceError::Poisoned`] if the initializer panicked on an earlier
+    /// access.
+    pub fn try_into_inner(this: Self) -> Result<T, OnceError<F>> {
+        let completed = this.once.is_completed();
+        let poisoned = this.once.is_poisoned();
+
+        // Take the slot out by hand, as dropping the lazy would drop its contents.
+        let mut this = ManuallyDrop::new(this);
+        let data = unsafe { ptr::read(this.data.get()) };
+        unsafe { ptr::drop_in_place(&mut this.once) };
+
+        if completed {
+            Ok(ManuallyDrop::into_inner(unsafe { data.value }))
+        } else if poisoned {
+            Err(OnceError::Poisoned)
+        } else {
+            Err(OnceError::Failed(ManuallyDrop::into_inner(unsafe {
+                data.init
+            })))
         }
     }
+
+    /// Check if the initializer panicked, leaving the value unable to be initialized.
+    pub fn is_poisoned(this: &Self) -> bool {
+        this.once.is_poisoned()
+    }
 }
 
 impl<R, T, F> LazyLock<R, T, F>
@@ -27,19 +108,60 @@ where
     R: RawOnce,
     F: FnOnce() -> T,
 {
+    /// # Panics
+    /// Panics if the initializer panicked on an earlier access, or if called from within
+    /// the initializer.
     pub fn force(this: &Self) -> &T {
-        this.cell.get_or_init(|| {
-            let init = unsafe { this.init.take().unwrap_unchecked() };
-            init()
-        })
+        match Self::try_force(this) {
+            Ok(value) => value,
+            Err(OnceError::Poisoned) => panic!("LazyLock instance has previously been poisoned"),
+            Err(OnceError::Reentrant) => reentrant(),
+            Err(OnceError::Failed(err)) => match err {},
+        }
+    }
+
+    /// Like [`LazyLock::force`], but reports poisoning and reentrancy instead of panicking.
+    pub fn try_force(this: &Self) -> Result<&T, OnceError> {
+        if let Some(value) = Self::get(this) {
+            return Ok(value);
+        }
+        if this.once.is_reentrant() {
+            return Err(OnceError::Reentrant);
+        }
+
+        call_erased(&this.once, |state| {
+            // The initializer is taken by the call that runs it. If it panicked, there is
+            // nothing left to run.
+            if state.is_poisoned() {
+                state.set_skipped(true);
+                return Err(OnceError::Poisoned);
+            }
+            let data = this.data.get();
+            let init = unsafe { ManuallyDrop::take(&mut (*data).init) };
+            let value = init();
+            unsafe { (*data).value = ManuallyDrop::new(value) };
+            Ok(())
+        })?;
+        Ok(unsafe { &(*this.data.get()).value })
     }
 
+    /// # Panics
+    /// Panics if the initializer panicked on an earlier access.
     pub fn force_mut(this: &mut Self) -> &mut T {
-        if this.cell.get_mut().is_none() {
-            let init = unsafe { this.init.take().unwrap_unchecked() };
-            this.cell = OnceLock::with_value(init());
+        match Self::try_force_mut(this) {
+            Ok(value) => value,
+            Err(OnceError::Poisoned) => panic!("LazyLock instance has previously been poisoned"),
+            Err(OnceError::Reentrant) => reentrant(),
+            Err(OnceError::Failed(err)) => match err {},
         }
-        unsafe { this.cell.get_mut().unwrap_unchecked() }
+    }
+
+    /// Like [`LazyLock::force_mut`], but reports poisoning instead of panicking.
+    pub fn try_force_mut(this: &mut Self) -> Result<&mut T, OnceError> {
+        // Go through the once even though we have exclusive access, so that a panicking
+        // initializer poisons it.
+        Self::try_force(this)?;
+        Ok(unsafe { Self::get_mut(this).unwrap_unchecked() })
     }
 }
 
@@ -77,10 +199,24 @@ where
     }
 }
 
+impl<R, T, F> Drop for LazyLock<R, T, F>
+where
+    R: RawOnce,
+{
+    fn drop(&mut self) {
+        let data = self.data.get_mut();
+        if self.once.is_completed() {
+            unsafe { ManuallyDrop::drop(&mut data.value) };
+        } else if !self.once.is_poisoned() {
+            unsafe { ManuallyDrop::drop(&mut data.init) };
+        }
+    }
+}
+
 unsafe impl<R, T, F> Sync for LazyLock<R, T, F>
 where
     T: Sync + Send,
-    R: Sync + Send,
+    R: RawOnce + Sync + Send,
     F: Send,
 {
 }
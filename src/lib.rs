@@ -1,15 +1,84 @@
 #![no_std]
 
+// The `std` backend takes the name at the crate root, so std is linked under another.
+#[cfg(any(feature = "std", test))]
+extern crate std as libstd;
+
+mod async_lazy;
+mod async_once_lock;
+#[cfg(feature = "std")]
+mod attempts;
+mod context;
+#[cfg(feature = "critical-section")]
+pub mod critical_section;
+mod error;
 mod exclusive_cell;
+#[cfg(all(
+    feature = "linux-futex",
+    target_os = "linux",
+    any(
+        target_arch = "x86_64",
+        target_arch = "aarch64",
+        target_arch = "riscv64"
+    )
+))]
+pub mod futex;
 mod lazy;
+#[cfg(any(
+    feature = "std",
+    feature = "spin",
+    feature = "critical-section",
+    feature = "linux-futex"
+))]
+mod limit;
+mod notify;
 mod once;
 mod once_lock;
+mod once_result;
+#[cfg(feature = "parking_lot")]
+pub mod parking_lot;
+mod poison;
+#[cfg(feature = "spin")]
+pub mod spin;
+mod state;
+#[cfg(feature = "std")]
+pub mod std;
+#[cfg(all(
+    test,
+    any(
+        feature = "spin",
+        feature = "std",
+        feature = "critical-section",
+        feature = "parking_lot",
+        feature = "linux-futex"
+    )
+))]
+mod testing;
+mod timeout;
+mod try_lazy;
+mod waker_list;
 
 use core::convert::Infallible;
 
+pub use async_lazy::AsyncLazy;
+pub use async_once_lock::AsyncOnceLock;
+#[cfg(feature = "std")]
+pub use attempts::RawAttemptsOnce;
+#[cfg(feature = "std")]
+pub use context::StdThread;
+pub use context::{Context, OwnerStorage, Unidentified};
+pub use error::{OnceError, WouldBlock};
+pub use exclusive_cell::{ExclusiveCell, TryInitError};
 pub use lazy::LazyLock;
-pub use once::{Once, OnceState, RawOnce};
-pub use once_lock::OnceLock;
+pub use notify::RawNotifyOnce;
+pub use once::{
+    Once, OnceState, RawOnce, RawOnceNonBlocking, RawOnceSplit, RawOnceTimed, RawOnceWait,
+};
+pub use once_lock::{BeginInit, BrandedOnceLock, InitGuard, OnceLock, OnceToken};
+pub use once_result::OnceResult;
+pub use poison::{ErrorOnPoison, IgnorePoison, PanicOnPoison, PoisonPolicy};
+pub use timeout::{Deadline, Timeout};
+pub use try_lazy::{CacheError, RetryOnError, TryLazyLock, TryLazyPolicy};
 
 fn into_ok<T>(result: Result<T, Infallible>) -> T {
     match result {
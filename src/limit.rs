@@ -0,0 +1,51 @@
+use core::{convert::Infallible, time::Duration};
+
+use crate::{Deadline, Timeout, WouldBlock};
+
+/// How long a backend may keep blocking for.
+///
+/// Untimed operations use [`Unlimited`], whose error is [`Infallible`], so the backends can
+/// share one implementation between their timed and untimed paths.
+pub(crate) trait Limit {
+    type Error;
+
+    /// The longest the caller may block for next, `None` meaning forever, or an error if
+    /// it must stop waiting.
+    fn remaining(&self) -> Result<Option<Duration>, Self::Error>;
+}
+
+pub(crate) struct Unlimited;
+
+impl Limit for Unlimited {
+    type Error = Infallible;
+
+    #[inline]
+    fn remaining(&self) -> Result<Option<Duration>, Infallible> {
+        Ok(None)
+    }
+}
+
+pub(crate) struct Until<'a, D>(pub &'a D);
+
+impl<D> Limit for Until<'_, D>
+where
+    D: Deadline,
+{
+    type Error = Timeout;
+
+    fn remaining(&self) -> Result<Option<Duration>, Timeout> {
+        self.0.remaining().map(Some).ok_or(Timeout)
+    }
+}
+
+/// Refuses to block at all.
+pub(crate) struct NonBlocking;
+
+impl Limit for NonBlocking {
+    type Error = WouldBlock;
+
+    #[inline]
+    fn remaining(&self) -> Result<Option<Duration>, WouldBlock> {
+        Err(WouldBlock)
+    }
+}
@@ -0,0 +1,154 @@
+use core::cell::Cell;
+
+use crate::{
+    waker_list::{Waiter, WakerList},
+    Deadline, OnceState, RawOnce, RawOnceNonBlocking, RawOnceSplit, RawOnceTimed, RawOnceWait,
+    Timeout, WouldBlock,
+};
+
+/// Wraps a [`RawOnce`] to wake async tasks whenever it leaves the running state.
+///
+/// Cells using it can be awaited with [`OnceLock::wait_async`](crate::OnceLock::wait_async)
+/// while being initialized from blocking code, so sync and async code can share one cell.
+/// Tasks are woken after the wrapped once has published its new state, on completion as
+/// well as on poisoning.
+///
+/// Registering and waking tasks takes a lock. Without the `critical-section` feature it is
+/// a spin lock, so the once must neither be awaited nor initialized from contexts that can
+/// preempt each other, such as thread mode and an interrupt handler on one core. With the
+/// feature, the lock is a critical section and initializing from an interrupt is fine.
+pub struct RawNotifyOnce<R> {
+    raw: R,
+    wakers: WakerList,
+}
+
+impl<R> RawNotifyOnce<R> {
+    /// A place in the queue of tasks to wake.
+    pub(crate) fn waiter(&self) -> Waiter<'_> {
+        Waiter::new(&self.wakers)
+    }
+
+    fn notify(&self) -> Notify<'_> {
+        Notify {
+            wakers: &self.wakers,
+            ran: Cell::new(false),
+        }
+    }
+}
+
+unsafe impl<R> RawOnce for RawNotifyOnce<R>
+where
+    R: RawOnce,
+{
+    #[allow(clippy::declare_interior_mutable_const)]
+    const COMPLETE: Self = Self {
+        raw: R::COMPLETE,
+        wakers: WakerList::new(),
+    };
+    #[allow(clippy::declare_interior_mutable_const)]
+    const INCOMPLETE: Self = Self {
+        raw: R::INCOMPLETE,
+        wakers: WakerList::new(),
+    };
+
+    #[inline]
+    fn is_completed(&self) -> bool {
+        self.raw.is_completed()
+    }
+
+    fn is_poisoned(&self) -> bool {
+        self.raw.is_poisoned()
+    }
+
+    fn is_reentrant(&self) -> bool {
+        self.raw.is_reentrant()
+    }
+
+    fn call_dyn(&self, f: &mut dyn FnMut(&OnceState) -> bool) {
+        let notify = self.notify();
+        self.raw.call_dyn(&mut |state| {
+            notify.ran.set(true);
+            f(state)
+        })
+    }
+}
+
+unsafe impl<R> RawOnceWait for RawNotifyOnce<R>
+where
+    R: RawOnceWait,
+{
+    fn wait(&self, ignore_poisoning: bool) -> bool {
+        self.raw.wait(ignore_poisoning)
+    }
+}
+
+unsafe impl<R> RawOnceTimed for RawNotifyOnce<R>
+where
+    R: RawOnceTimed,
+{
+    fn call_until<F, E, D>(&self, f: F, deadline: &D) -> Result<Result<(), E>, Timeout>
+    where
+        F: FnOnce(&OnceState) -> Result<(), E>,
+        D: Deadline,
+    {
+        let notify = self.notify();
+        self.raw.call_until(
+            |state| {
+                notify.ran.set(true);
+                f(state)
+            },
+            deadline,
+        )
+    }
+
+    fn wait_until<D>(&self, ignore_poisoning: bool, deadline: &D) -> Result<bool, Timeout>
+    where
+        D: Deadline,
+    {
+        self.raw.wait_until(ignore_poisoning, deadline)
+    }
+}
+
+unsafe impl<R> RawOnceNonBlocking for RawNotifyOnce<R>
+where
+    R: RawOnceNonBlocking,
+{
+    fn try_call<F, E>(&self, f: F) -> Result<Result<(), E>, WouldBlock>
+    where
+        F: FnOnce(&OnceState) -> Result<(), E>,
+    {
+        let notify = self.notify();
+        self.raw.try_call(|state| {
+            notify.ran.set(true);
+            f(state)
+        })
+    }
+}
+
+unsafe impl<R> RawOnceSplit for RawNotifyOnce<R>
+where
+    R: RawOnceSplit,
+{
+    fn acquire(&self) -> Option<OnceState> {
+        self.raw.acquire()
+    }
+
+    unsafe fn release(&self, complete: bool) {
+        unsafe { self.raw.release(complete) };
+        self.wakers.wake_all();
+    }
+}
+
+/// Wakes every queued task on drop if the initializer ran, including when it panicked.
+struct Notify<'a> {
+    wakers: &'a WakerList,
+    ran: Cell<bool>,
+}
+
+impl Drop for Notify<'_> {
+    fn drop(&mut self) {
+        if self.ran.get() {
+            self.wakers.wake_all();
+        }
+    }
+}
@@ -1,17 +1,49 @@
-use core::{convert::Infallible, fmt::Debug};
+#[cfg(feature = "std")]
+use core::sync::atomic::{AtomicBool, Ordering};
+use core::{convert::Infallible, fmt::Debug, marker::PhantomData};
+#[cfg(feature = "std")]
+use libstd::string::String;
 
+use crate::{
+    error::{flatten, unwrap_failed, unwrap_once},
+    poison::{self, PanicOnPoison, PoisonPolicy},
+    Deadline, OnceError, Timeout, WouldBlock,
+};
+
+/// What an initializer is told about the once it is running on.
+///
+/// With the `std` feature, a once wrapped in a `RawAttemptsOnce`, as the aliases in
+#[cfg_attr(feature = "std", doc = "[`sync_api::std`](crate::std) are,")]
+#[cfg_attr(not(feature = "std"), doc = "`sync_api::std` are,")]
+/// also reports how many attempts have been made and why an earlier one panicked.
 #[derive(Debug)]
 pub struct OnceState {
     is_poisoned: bool,
+    #[cfg(feature = "std")]
+    attempt: u32,
+    #[cfg(feature = "std")]
+    panic_message: Option<String>,
+    #[cfg(feature = "std")]
+    skipped: AtomicBool,
 }
 
 impl OnceState {
     pub fn new() -> Self {
-        Self { is_poisoned: false }
+        Self {
+            is_poisoned: false,
+            #[cfg(feature = "std")]
+            attempt: 0,
+            #[cfg(feature = "std")]
+            panic_message: None,
+            #[cfg(feature = "std")]
+            skipped: AtomicBool::new(false),
+        }
     }
 
     pub fn poisoned() -> Self {
-        Self { is_poisoned: true }
+        let mut state = Self::new();
+        state.poison();
+        state
     }
 
     pub fn poison(&mut self) -> &mut Self {
@@ -22,6 +54,46 @@ impl OnceState {
     pub fn is_poisoned(&self) -> bool {
         self.is_poisoned
     }
+
+    /// The number of this attempt at initializing the once, counting from 1, or 0 if the
+    /// once does not keep count.
+    #[cfg(feature = "std")]
+    pub fn attempt(&self) -> u32 {
+        self.attempt
+    }
+
+    #[cfg(feature = "std")]
+    pub fn set_attempt(&mut self, attempt: u32) -> &mut Self {
+        self.attempt = attempt;
+        self
+    }
+
+    /// The message of the first panic that poisoned the once, if any.
+    ///
+    /// Panics with a payload other than a string are reported as `"Box<dyn Any>"`.
+    #[cfg(feature = "std")]
+    pub fn panic_message(&self) -> Option<&str> {
+        self.panic_message.as_deref()
+    }
+
+    #[cfg(feature = "std")]
+    pub fn set_panic_message(&mut self, message: Option<String>) -> &mut Self {
+        self.panic_message = message;
+        self
+    }
+
+    /// Records whether the initializer handed this state was refused to run, so that it
+    /// is not counted as an attempt.
+    #[cfg_attr(not(feature = "std"), allow(unused_variables))]
+    pub(crate) fn set_skipped(&self, skipped: bool) {
+        #[cfg(feature = "std")]
+        self.skipped.store(skipped, Ordering::Relaxed);
+    }
+
+    #[cfg(feature = "std")]
+    pub(crate) fn is_skipped(&self) -> bool {
+        self.skipped.load(Ordering::Relaxed)
+    }
 }
 
 impl Default for OnceState {
@@ -30,8 +102,12 @@ impl Default for OnceState {
     }
 }
 
-pub struct Once<R> {
+/// A synchronization primitive that runs an initializer exactly once.
+///
+/// `P` chooses what happens once an initializer panicked or failed, see [`PoisonPolicy`].
+pub struct Once<R, P = PanicOnPoison> {
     raw: R,
+    _policy: PhantomData<fn() -> P>,
 }
 
 impl<R> Once<R>
@@ -39,56 +115,82 @@ where
     R: RawOnce,
 {
     pub const fn new() -> Self {
-        Self { raw: R::INCOMPLETE }
+        Self::with_policy()
     }
+}
 
+impl<R, P> Once<R, P>
+where
+    R: RawOnce,
+    P: PoisonPolicy,
+{
+    /// Like [`Once::new`], but for any [`PoisonPolicy`].
+    pub const fn with_policy() -> Self {
+        Self {
+            raw: R::INCOMPLETE,
+            _policy: PhantomData,
+        }
+    }
+
+    /// # Panics
+    /// Panics if the once is poisoned and `P` does not ignore it, or if called from within
+    /// the once's own initializer.
     pub fn call_once<F>(&self, f: F)
     where
         F: FnOnce(),
     {
-        self.call_once_force(|state| {
-            if state.is_poisoned() {
-                panic!("Once poisoned");
-            }
+        unwrap_once(self.try_call_once(|| {
             f();
-        })
+            Ok::<_, Infallible>(())
+        }))
     }
 
     pub fn call_once_force<F>(&self, f: F)
     where
         F: FnOnce(&OnceState),
     {
-        let r = self.try_call_once_force(|once_state| {
+        unwrap_once(self.try_call_once_force(|once_state| {
             f(once_state);
             Ok::<_, Infallible>(())
-        });
-
-        match r {
-            Ok(_) => {}
-            Err(e) => match e {},
-        }
+        }))
     }
 
-    pub fn try_call_once<F, E>(&self, f: F) -> Result<(), E>
+    /// Like [`Once::call_once`], but reports failure, poisoning and reentrancy instead of
+    /// panicking.
+    ///
+    /// # Errors
+    /// Returns [`OnceError::Reentrant`] if called from within this once's own initializer,
+    /// and [`OnceError::Poisoned`] if the once is poisoned and `P` is [`ErrorOnPoison`].
+    ///
+    /// # Panics
+    /// Panics if the once is poisoned and `P` is [`PanicOnPoison`].
+    ///
+    /// [`ErrorOnPoison`]: crate::ErrorOnPoison
+    pub fn try_call_once<F, E>(&self, f: F) -> Result<(), OnceError<E>>
     where
         F: FnOnce() -> Result<(), E>,
     {
-        self.try_call_once_force(|state| {
-            if state.is_poisoned() {
-                panic!("Once poisoned")
+        let res = self.try_call_once_force(|state| {
+            if !poison::should_run::<P>(state) {
+                return Err(OnceError::Poisoned);
             }
-            f()
-        })
+            f().map_err(OnceError::Failed)
+        });
+        flatten(res)
     }
 
-    pub fn try_call_once_force<F, E>(&self, f: F) -> Result<(), E>
+    /// # Errors
+    /// Returns [`OnceError::Reentrant`] if called from within this once's own initializer.
+    pub fn try_call_once_force<F, E>(&self, f: F) -> Result<(), OnceError<E>>
     where
         F: FnOnce(&OnceState) -> Result<(), E>,
     {
         if self.is_completed() {
             Ok(())
+        } else if self.raw.is_reentrant() {
+            Err(OnceError::Reentrant)
         } else {
-            self.raw.call(f)
+            call_erased(&self.raw, f).map_err(OnceError::Failed)
         }
     }
 
@@ -97,9 +199,161 @@ where
     }
 }
 
-impl<R> Debug for Once<R>
+impl<R, P> Once<R, P>
+where
+    R: RawOnceWait,
+    P: PoisonPolicy,
+{
+    /// Block until the once has completed, without running an initializer.
+    ///
+    /// # Panics
+    /// Panics if the once is or becomes poisoned, or if called from within its own
+    /// initializer.
+    pub fn wait(&self) {
+        unwrap_once(self.try_wait())
+    }
+
+    /// Like [`Once::wait`], but reports poisoning and reentrancy instead of panicking.
+    ///
+    /// # Errors
+    /// Returns [`OnceError::Poisoned`] if the once is or becomes poisoned, and
+    /// [`OnceError::Reentrant`] if called from within its own initializer.
+    pub fn try_wait(&self) -> Result<(), OnceError> {
+        if self.is_completed() {
+            Ok(())
+        } else if self.raw.is_reentrant() {
+            Err(OnceError::Reentrant)
+        } else if self.raw.wait(false) {
+            Ok(())
+        } else {
+            Err(OnceError::Poisoned)
+        }
+    }
+
+    /// Block until the once has completed, without running an initializer, ignoring
+    /// poisoning.
+    ///
+    /// # Panics
+    /// Panics if called from within the once's own initializer.
+    pub fn wait_force(&self) {
+        unwrap_once(self.try_wait_force())
+    }
+
+    /// Like [`Once::wait_force`], but reports reentrancy instead of panicking.
+    ///
+    /// # Errors
+    /// Returns [`OnceError::Reentrant`] if called from within the once's own initializer.
+    pub fn try_wait_force(&self) -> Result<(), OnceError> {
+        if self.is_completed() {
+            Ok(())
+        } else if self.raw.is_reentrant() {
+            Err(OnceError::Reentrant)
+        } else {
+            self.raw.wait(true);
+            Ok(())
+        }
+    }
+}
+
+impl<R, P> Once<R, P>
+where
+    R: RawOnceTimed,
+    P: PoisonPolicy,
+{
+    /// Like [`Once::call_once`], but gives up waiting for another caller once `deadline`
+    /// passes.
+    ///
+    /// # Panics
+    /// Panics if the once is poisoned and `P` does not ignore it, or if called from within
+    /// its own initializer.
+    pub fn call_once_timeout<F, D>(&self, f: F, deadline: D) -> Result<(), Timeout>
+    where
+        F: FnOnce(),
+        D: Deadline,
+    {
+        unwrap_failed(self.try_call_once_timeout(f, deadline))
+    }
+
+    /// Like [`Once::call_once_timeout`], but reports poisoning and reentrancy instead of
+    /// panicking.
+    ///
+    /// # Errors
+    /// Returns [`OnceError::Failed`] with [`Timeout`] once `deadline` passes,
+    /// [`OnceError::Reentrant`] if called from within the once's own initializer, and
+    /// [`OnceError::Poisoned`] if the once is poisoned and `P` is [`ErrorOnPoison`].
+    ///
+    /// [`ErrorOnPoison`]: crate::ErrorOnPoison
+    pub fn try_call_once_timeout<F, D>(&self, f: F, deadline: D) -> Result<(), OnceError<Timeout>>
+    where
+        F: FnOnce(),
+        D: Deadline,
+    {
+        if self.is_completed() {
+            return Ok(());
+        }
+        if self.raw.is_reentrant() {
+            return Err(OnceError::Reentrant);
+        }
+
+        self.raw
+            .call_until(
+                |state| {
+                    if !poison::should_run::<P>(state) {
+                        return Err(OnceError::Poisoned);
+                    }
+                    f();
+                    Ok(())
+                },
+                &deadline,
+            )
+            .map_err(OnceError::Failed)?
+    }
+}
+
+impl<R, P> Once<R, P>
+where
+    R: RawOnceNonBlocking,
+    P: PoisonPolicy,
+{
+    /// Like [`Once::call_once`], but fails instead of waiting if another caller is running
+    /// its initializer.
+    ///
+    /// `f` is only called if no one else is.
+    ///
+    /// # Errors
+    /// Returns [`OnceError::Failed`] with [`WouldBlock`] if another caller is running the
+    /// initializer, which includes calling this from within the once's own initializer.
+    /// Returns [`OnceError::Poisoned`] if the once is poisoned and `P` is
+    /// [`ErrorOnPoison`].
+    ///
+    /// # Panics
+    /// Panics if the once is poisoned and `P` is [`PanicOnPoison`].
+    ///
+    /// [`ErrorOnPoison`]: crate::ErrorOnPoison
+    pub fn try_call_once_nonblocking<F>(&self, f: F) -> Result<(), OnceError<WouldBlock>>
+    where
+        F: FnOnce(),
+    {
+        if self.is_completed() {
+            return Ok(());
+        }
+
+        self.raw
+            .try_call(|state| {
+                if !poison::should_run::<P>(state) {
+                    return Err(OnceError::Poisoned);
+                }
+                f();
+                Ok(())
+            })
+            .map_err(OnceError::Failed)?
+    }
+}
+
+impl<R, P> Debug for Once<R, P>
 where
     R: RawOnce,
+    P: PoisonPolicy,
 {
     fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         f.debug_struct("Once")
@@ -108,12 +362,13 @@ where
     }
 }
 
-impl<R> Default for Once<R>
+impl<R, P> Default for Once<R, P>
 where
     R: RawOnce,
+    P: PoisonPolicy,
 {
     fn default() -> Self {
-        Self::new()
+        Self::with_policy()
     }
 }
 
@@ -121,7 +376,20 @@ where
 ///
 /// # Safety
 /// The `[try_]call_once` methods must work correctly. Other primitives depend on this
-/// contract for their own safety.
+/// contract for their own safety, and in particular on the following:
+///
+/// - [`RawOnce::call_dyn`] calls `f` at most once per call, and never while another
+///   initializer of the same once is running. It returns without calling `f` only once
+///   the once has completed.
+/// - When `f` returns `true`, the once is completed, and everything `f` did happens
+///   before any caller sees it completed. When `f` returns `false` or panics, the once
+///   is poisoned instead.
+/// - [`RawOnce::is_poisoned`], and [`OnceState::is_poisoned`] on the state handed to `f`,
+///   are accurate. [`LazyLock`](crate::LazyLock) moves its initializer out before running
+///   it, and relies on them to know whether its slot still holds the initializer or holds
+///   nothing at all, so a wrong answer drops or reads freed memory.
+/// - The operations of [`RawOnceTimed`] and [`RawOnceNonBlocking`] that run an
+///   initializer follow the same rules, except that they may give up without calling it.
 pub unsafe trait RawOnce {
     const INCOMPLETE: Self;
     const COMPLETE: Self;
@@ -129,6 +397,21 @@ pub unsafe trait RawOnce {
     /// Check if the once has completed successfully.
     fn is_completed(&self) -> bool;
 
+    /// Check if the once is poisoned, meaning that the last initializer to run failed or
+    /// panicked and no other has completed it since.
+    fn is_poisoned(&self) -> bool;
+
+    /// Check if the caller is running inside this once's own initializer.
+    ///
+    /// Higher level types check this before calling or waiting on the once, which would
+    /// otherwise never return. Implementations that cannot tell which execution context
+    /// is running the initializer may keep the default of `false`, in which case a
+    /// reentrant call deadlocks.
+    #[inline]
+    fn is_reentrant(&self) -> bool {
+        false
+    }
+
     /// Call a function exactly once.
     ///
     /// Multiple threads may call this function, but only one function will be executed.
@@ -140,14 +423,121 @@ pub unsafe trait RawOnce {
     /// should pass a poisoned once state to the function, and the higher level type
     /// will handle poisoning correctly.
     ///
-    /// This is intentionally monomorphic and as flexible as possible
-    /// to minimize code size. Think of it as a monomorphized `try_call_once_force`,
-    /// if something like it existed in std.
+    /// This takes the function as a trait object, so that it is instantiated only once
+    /// however many initializers a program has, to minimize code size. Think of it as a
+    /// monomorphized `try_call_once_force`, if something like it existed in std.
     ///
     /// This is a single function designed to be called in the slow
-    /// path, so implementations will likely wish to annotate it with #[cold] and
-    /// #[inline(never)]
+    /// path, so implementations will likely wish to annotate it with `#[cold]` and
+    /// `#[inline(never)]`
+    fn call_dyn(&self, f: &mut dyn FnMut(&OnceState) -> bool);
+
+    /// Call a function exactly once, passing on the error it fails with.
+    ///
+    /// Forwards to [`RawOnce::call_dyn`], which implementations should leave as is to keep
+    /// the slow path shared between initializers.
+    #[inline]
     fn call<F, E>(&self, f: F) -> Result<(), E>
+    where
+        F: FnOnce(&OnceState) -> Result<(), E>,
+    {
+        call_erased(self, f)
+    }
+}
+
+/// Run `f` through [`RawOnce::call_dyn`], whatever `call` an implementation provides.
+#[inline]
+pub(crate) fn call_erased<R, F, E>(raw: &R, f: F) -> Result<(), E>
+where
+    R: RawOnce + ?Sized,
+    F: FnOnce(&OnceState) -> Result<(), E>,
+{
+    let mut f = Some(f);
+    let mut res = Ok(());
+
+    raw.call_dyn(&mut |state| {
+        // Implementations call the function at most once.
+        let Some(f) = f.take() else { return false };
+        match f(state) {
+            Ok(()) => true,
+            Err(err) => {
+                res = Err(err);
+                false
+            }
+        }
+    });
+    res
+}
+
+/// A [`RawOnce`] that can block until another caller completes it.
+///
+/// # Safety
+/// `wait` must only return `true` once the once has completed, and must synchronize with
+/// the completing call the same way [`RawOnce::is_completed`] does.
+pub unsafe trait RawOnceWait: RawOnce {
+    /// Block until the once completes, without running anything.
+    ///
+    /// Returns `true` once the once is complete. If `ignore_poisoning` is `false`, this
+    /// also returns `false` as soon as the once is observed to be poisoned. Otherwise a
+    /// poisoned once is waited on until some other caller completes it.
+    fn wait(&self, ignore_poisoning: bool) -> bool;
+}
+
+/// A [`RawOnceWait`] that can give up waiting at a deadline.
+///
+/// # Safety
+/// `call_until` must uphold the contract of [`RawOnce::call`] whenever it runs `f`, and
+/// `wait_until` must uphold the contract of [`RawOnceWait::wait`] whenever it returns `Ok`.
+pub unsafe trait RawOnceTimed: RawOnceWait {
+    /// Like [`RawOnce::call`], but stops waiting for another caller's initializer once
+    /// `deadline` passes.
+    ///
+    /// The deadline only bounds waiting. Once `f` has started, it always runs to completion.
+    fn call_until<F, E, D>(&self, f: F, deadline: &D) -> Result<Result<(), E>, Timeout>
+    where
+        F: FnOnce(&OnceState) -> Result<(), E>,
+        D: Deadline;
+
+    /// Like [`RawOnceWait::wait`], but gives up once `deadline` passes.
+    fn wait_until<D>(&self, ignore_poisoning: bool, deadline: &D) -> Result<bool, Timeout>
+    where
+        D: Deadline;
+}
+
+/// A [`RawOnce`] that can refuse to wait for another caller's initializer.
+///
+/// # Safety
+/// `try_call` must uphold the contract of [`RawOnce::call`] whenever it runs `f`.
+pub unsafe trait RawOnceNonBlocking: RawOnce {
+    /// Like [`RawOnce::call`], but returns [`WouldBlock`] instead of waiting if the once is
+    /// running.
+    ///
+    /// Never blocks or spins on another caller. `f` is only called if the once was
+    /// acquired.
+    fn try_call<F, E>(&self, f: F) -> Result<Result<(), E>, WouldBlock>
     where
         F: FnOnce(&OnceState) -> Result<(), E>;
 }
+
+/// A [`RawOnce`] whose running state can be held across arbitrary code, rather than only
+/// for the duration of a closure.
+///
+/// # Safety
+/// `acquire` must only return `Some` once the caller is the only one running the once, and
+/// `release` must then uphold the contract of [`RawOnce::call`] as if the initializer had
+/// succeeded if `complete` is set, and failed otherwise.
+pub unsafe trait RawOnceSplit: RawOnce {
+    /// Move the once into the running state, blocking while another caller holds it.
+    ///
+    /// Returns `None` if the once is complete. Otherwise the caller holds the running state
+    /// until it calls [`RawOnceSplit::release`].
+    fn acquire(&self) -> Option<OnceState>;
+
+    /// Leave the running state, completing the once if `complete` is set and poisoning it
+    /// otherwise.
+    ///
+    /// # Safety
+    /// Must be called exactly once for each call to [`RawOnceSplit::acquire`] that
+    /// returned `Some`, from the same context.
+    unsafe fn release(&self, complete: bool);
+}
@@ -1,11 +1,55 @@
-use core::{cell::UnsafeCell, convert::Infallible, fmt::Debug, mem};
+use core::{
+    cell::UnsafeCell,
+    convert::Infallible,
+    fmt::Debug,
+    future::Future,
+    marker::PhantomData,
+    mem::{self, MaybeUninit},
+    ops::Deref,
+    pin::Pin,
+    task::{Context, Poll},
+};
 
-use super::once::RawOnce;
-use crate::into_ok;
+use super::once::{
+    call_erased, OnceState, RawOnce, RawOnceNonBlocking, RawOnceSplit, RawOnceTimed, RawOnceWait,
+};
+use crate::{
+    error::{flatten, unwrap_failed, unwrap_once},
+    into_ok,
+    poison::{self, IgnorePoison, PoisonPolicy},
+    waker_list::Waiter,
+    Deadline, OnceError, RawNotifyOnce, Timeout, WouldBlock,
+};
 
-pub struct OnceLock<R, T> {
+/// A cell that is written at most once.
+///
+/// `P` chooses what happens once an initializer panicked or failed, see [`PoisonPolicy`].
+/// By default the next initializer simply runs.
+///
+/// # Drop check
+/// The value is stored uninitialized until the once completes, so the cell drops it by
+/// hand, and that needs a [`Drop`] impl that stable Rust cannot mark as leaving `T` alone.
+/// A cell must therefore be dropped before anything its value borrows from, which it did
+/// not have to be while the value was kept in an [`Option`]. This is a breaking change:
+///
+/// ```compile_fail,E0597
+/// # use sync_api::{OnceLock, RawOnce};
+/// fn borrow<R: RawOnce>() {
+///     let cell = OnceLock::<R, &String>::new();
+///     let s = String::new();
+///     cell.set(&s).unwrap();
+/// }
+/// ```
+///
+/// Declaring the borrowed value first, so that it is dropped last, compiles again.
+pub struct OnceLock<R, T, P = IgnorePoison>
+where
+    R: RawOnce,
+{
     once: R,
-    value: UnsafeCell<Option<T>>,
+    // Initialized exactly when `once` is complete.
+    value: UnsafeCell<MaybeUninit<T>>,
+    _policy: PhantomData<fn() -> P>,
 }
 
 impl<R, T> OnceLock<R, T>
@@ -13,16 +57,34 @@ where
     R: RawOnce,
 {
     pub const fn new() -> Self {
+        Self::with_policy()
+    }
+
+    pub const fn with_value(value: T) -> Self {
+        Self::with_value_and_policy(value)
+    }
+}
+
+impl<R, T, P> OnceLock<R, T, P>
+where
+    R: RawOnce,
+    P: PoisonPolicy,
+{
+    /// Like [`OnceLock::new`], but for any [`PoisonPolicy`].
+    pub const fn with_policy() -> Self {
         Self {
             once: R::INCOMPLETE,
-            value: UnsafeCell::new(None),
+            value: UnsafeCell::new(MaybeUninit::uninit()),
+            _policy: PhantomData,
         }
     }
 
-    pub const fn with_value(value: T) -> Self {
+    /// Like [`OnceLock::with_value`], but for any [`PoisonPolicy`].
+    pub const fn with_value_and_policy(value: T) -> Self {
         Self {
             once: R::COMPLETE,
-            value: UnsafeCell::new(Some(value)),
+            value: UnsafeCell::new(MaybeUninit::new(value)),
+            _policy: PhantomData,
         }
     }
 
@@ -34,74 +96,172 @@ where
         }
     }
 
+    /// Hands `f` the cell under a brand of its own, so that the [`OnceToken`]s it hands
+    /// out are only accepted by this cell.
+    pub fn brand<'a, F, U>(&'a self, f: F) -> U
+    where
+        F: for<'id> FnOnce(BrandedOnceLock<'id, 'a, R, T, P>) -> U,
+    {
+        f(BrandedOnceLock {
+            cell: self,
+            _brand: PhantomData,
+        })
+    }
+
     pub fn get_mut(&mut self) -> Option<&mut T> {
-        self.value.get_mut().as_mut()
+        if self.once.is_completed() {
+            Some(unsafe { self.value.get_mut().assume_init_mut() })
+        } else {
+            None
+        }
     }
 
     /// # Safety
     /// This once cell must be initialized
     pub unsafe fn get_unchecked(&self) -> &T {
-        unsafe { (*self.value.get()).as_ref().unwrap_unchecked() }
+        unsafe { (*self.value.get()).assume_init_ref() }
     }
 
-    pub fn into_inner(self) -> Option<T> {
-        self.value.into_inner()
+    pub fn into_inner(mut self) -> Option<T> {
+        self.take()
     }
 
     pub fn take(&mut self) -> Option<T> {
-        mem::take(self).into_inner()
+        let completed = self.once.is_completed();
+        // Start over like a new cell, which also keeps the value from being dropped again.
+        self.once = R::INCOMPLETE;
+        if completed {
+            Some(unsafe { self.value.get_mut().assume_init_read() })
+        } else {
+            None
+        }
     }
 
+    /// Store `value` as the value of the cell.
+    ///
+    /// # Safety
+    /// Must only be called by the caller running the once, right before completing it.
+    unsafe fn init_value(&self, value: T) {
+        unsafe { (*self.value.get()).write(value) };
+    }
+
+    /// # Panics
+    /// Panics if called from within the cell's own initializer.
     pub fn set(&self, value: T) -> Result<(), T> {
+        unwrap_failed(self.try_set(value))
+    }
+
+    /// Like [`OnceLock::set`], but reports reentrancy instead of panicking.
+    ///
+    /// # Errors
+    /// Returns [`OnceError::Failed`] with the value if the cell was already initialized,
+    /// and [`OnceError::Reentrant`], dropping the value, if called from within the cell's
+    /// own initializer.
+    pub fn try_set(&self, value: T) -> Result<(), OnceError<T>> {
         if self.get().is_some() {
-            return Err(value);
+            return Err(OnceError::Failed(value));
+        }
+        if self.once.is_reentrant() {
+            return Err(OnceError::Reentrant);
         }
 
         let mut value = Some(value);
 
-        let res = self.once.call(|_| unsafe {
-            *self.value.get() = value.take();
+        let res = call_erased(&self.once, |_| {
+            if let Some(value) = value.take() {
+                unsafe { self.init_value(value) };
+            }
             Ok::<_, Infallible>(())
         });
         into_ok(res);
 
         match value {
-            Some(value) => Err(value),
+            Some(value) => Err(OnceError::Failed(value)),
             None => Ok(()),
         }
     }
 
+    /// # Panics
+    /// Panics if called from within the cell's own initializer.
     pub fn try_insert(&self, value: T) -> Result<&T, (&T, T)> {
-        let result = self.set(value);
-        let present = unsafe { self.get_unchecked() };
+        unwrap_failed(self.try_insert_checked(value))
+    }
 
+    /// Like [`OnceLock::try_insert`], but reports reentrancy instead of panicking.
+    ///
+    /// # Errors
+    /// Returns [`OnceError::Failed`] with the present value and the given one if the cell
+    /// was already initialized, and [`OnceError::Reentrant`], dropping the value, if called
+    /// from within the cell's own initializer.
+    pub fn try_insert_checked(&self, value: T) -> Result<&T, OnceError<(&T, T)>> {
+        let result = self.try_set(value);
+        // Unless reentered, the cell is initialized either way.
         match result {
-            Ok(_) => Ok(present),
-            Err(value) => Err((present, value)),
+            Ok(()) => Ok(unsafe { self.get_unchecked() }),
+            Err(OnceError::Failed(value)) => {
+                Err(OnceError::Failed((unsafe { self.get_unchecked() }, value)))
+            }
+            Err(OnceError::Reentrant) => Err(OnceError::Reentrant),
+            Err(OnceError::Poisoned) => Err(OnceError::Poisoned),
         }
     }
 
+    /// # Panics
+    /// Panics if the cell is poisoned and `P` does not ignore it, or if called from within
+    /// the cell's own initializer.
     pub fn get_or_init<F>(&self, f: F) -> &T
     where
         F: FnOnce() -> T,
     {
-        into_ok(self.get_or_try_init::<_, Infallible>(|| Ok(f())))
+        unwrap_once(self.get_or_try_init(|| Ok(f())))
     }
 
-    pub fn get_or_try_init<F, E>(&self, f: F) -> Result<&T, E>
+    /// # Errors
+    /// Returns [`OnceError::Reentrant`] if called from within the cell's own initializer,
+    /// and [`OnceError::Poisoned`] if the cell is poisoned and `P` is [`ErrorOnPoison`].
+    ///
+    /// # Panics
+    /// Panics if the cell is poisoned and `P` is [`PanicOnPoison`].
+    ///
+    /// [`ErrorOnPoison`]: crate::ErrorOnPoison
+    /// [`PanicOnPoison`]: crate::PanicOnPoison
+    pub fn get_or_try_init<F, E>(&self, f: F) -> Result<&T, OnceError<E>>
     where
         F: FnOnce() -> Result<T, E>,
+    {
+        let res = self.get_or_try_init_force(|state| {
+            if !poison::should_run::<P>(state) {
+                return Err(OnceError::Poisoned);
+            }
+            f().map_err(OnceError::Failed)
+        });
+        flatten(res)
+    }
+
+    /// Whether an initializer panicked or failed, and none has succeeded since.
+    pub(crate) fn is_poisoned(&self) -> bool {
+        self.once.is_poisoned()
+    }
+
+    /// Like [`OnceLock::get_or_try_init`], but hands the initializer the state of the once
+    /// instead of applying `P`.
+    pub(crate) fn get_or_try_init_force<F, E>(&self, f: F) -> Result<&T, OnceError<E>>
+    where
+        F: FnOnce(&OnceState) -> Result<T, E>,
     {
         if let Some(value) = self.get() {
             Ok(value)
+        } else if self.once.is_reentrant() {
+            Err(OnceError::Reentrant)
         } else {
-            self.once.call(|_| {
-                let value = f()?;
+            call_erased(&self.once, |state| {
+                let value = f(state)?;
                 unsafe {
-                    *self.value.get() = Some(value);
+                    self.init_value(value);
                 }
                 Ok(())
-            })?;
+            })
+            .map_err(OnceError::Failed)?;
             Ok(unsafe { self.get_unchecked() })
         }
     }
@@ -125,23 +285,274 @@ where
     // }
 }
 
-impl<R, T> Clone for OnceLock<R, T>
+impl<R, T, P> OnceLock<R, T, P>
+where
+    R: RawOnceWait,
+    P: PoisonPolicy,
+{
+    /// Block until the cell has been initialized, without supplying an initializer.
+    ///
+    /// # Panics
+    /// Panics if called from within the cell's own initializer.
+    pub fn wait(&self) -> &T {
+        unwrap_once(self.try_wait())
+    }
+
+    /// Like [`OnceLock::wait`], but reports reentrancy instead of panicking.
+    ///
+    /// # Errors
+    /// Returns [`OnceError::Reentrant`] if called from within the cell's own initializer.
+    pub fn try_wait(&self) -> Result<&T, OnceError> {
+        if let Some(value) = self.get() {
+            return Ok(value);
+        }
+        if self.once.is_reentrant() {
+            return Err(OnceError::Reentrant);
+        }
+
+        self.once.wait(true);
+        Ok(unsafe { self.get_unchecked() })
+    }
+}
+
+impl<R, T, P> OnceLock<R, T, P>
+where
+    R: RawOnceTimed,
+    P: PoisonPolicy,
+{
+    /// Like [`OnceLock::wait`], but gives up once `deadline` passes.
+    ///
+    /// # Panics
+    /// Panics if called from within the cell's own initializer.
+    pub fn wait_timeout<D>(&self, deadline: D) -> Result<&T, Timeout>
+    where
+        D: Deadline,
+    {
+        unwrap_failed(self.try_wait_timeout(deadline))
+    }
+
+    /// Like [`OnceLock::wait_timeout`], but reports reentrancy instead of panicking.
+    ///
+    /// # Errors
+    /// Returns [`OnceError::Failed`] with [`Timeout`] once `deadline` passes, and
+    /// [`OnceError::Reentrant`] if called from within the cell's own initializer.
+    pub fn try_wait_timeout<D>(&self, deadline: D) -> Result<&T, OnceError<Timeout>>
+    where
+        D: Deadline,
+    {
+        if let Some(value) = self.get() {
+            return Ok(value);
+        }
+        if self.once.is_reentrant() {
+            return Err(OnceError::Reentrant);
+        }
+
+        self.once
+            .wait_until(true, &deadline)
+            .map_err(OnceError::Failed)?;
+        Ok(unsafe { self.get_unchecked() })
+    }
+
+    /// Like [`OnceLock::get_or_init`], but gives up waiting for another caller's
+    /// initializer once `deadline` passes.
+    ///
+    /// # Panics
+    /// Panics if the cell is poisoned and `P` does not ignore it, or if called from within
+    /// the cell's own initializer.
+    pub fn get_or_init_timeout<F, D>(&self, f: F, deadline: D) -> Result<&T, Timeout>
+    where
+        F: FnOnce() -> T,
+        D: Deadline,
+    {
+        unwrap_failed(self.try_get_or_init_timeout(f, deadline))
+    }
+
+    /// Like [`OnceLock::get_or_init_timeout`], but reports poisoning and reentrancy
+    /// instead of panicking.
+    ///
+    /// # Errors
+    /// Returns [`OnceError::Failed`] with [`Timeout`] once `deadline` passes,
+    /// [`OnceError::Reentrant`] if called from within the cell's own initializer, and
+    /// [`OnceError::Poisoned`] if the cell is poisoned and `P` is [`ErrorOnPoison`].
+    ///
+    /// # Panics
+    /// Panics if the cell is poisoned and `P` is [`PanicOnPoison`].
+    ///
+    /// [`ErrorOnPoison`]: crate::ErrorOnPoison
+    /// [`PanicOnPoison`]: crate::PanicOnPoison
+    pub fn try_get_or_init_timeout<F, D>(&self, f: F, deadline: D) -> Result<&T, OnceError<Timeout>>
+    where
+        F: FnOnce() -> T,
+        D: Deadline,
+    {
+        if let Some(value) = self.get() {
+            return Ok(value);
+        }
+        if self.once.is_reentrant() {
+            return Err(OnceError::Reentrant);
+        }
+
+        self.once
+            .call_until(
+                |state| {
+                    if !poison::should_run::<P>(state) {
+                        return Err(OnceError::Poisoned);
+                    }
+                    unsafe {
+                        self.init_value(f());
+                    }
+                    Ok(())
+                },
+                &deadline,
+            )
+            .map_err(OnceError::Failed)??;
+        Ok(unsafe { self.get_unchecked() })
+    }
+}
+
+impl<R, T, P> OnceLock<R, T, P>
+where
+    R: RawOnceNonBlocking,
+    P: PoisonPolicy,
+{
+    /// Like [`OnceLock::get_or_init`], but fails instead of waiting if another caller is
+    /// initializing the cell.
+    ///
+    /// `f` is only called if no one else is, so this is safe to use from contexts that
+    /// must never block, such as interrupt handlers.
+    ///
+    /// # Errors
+    /// Returns [`OnceError::Failed`] with [`WouldBlock`] if another caller is initializing
+    /// the cell, which includes calling this from within the cell's own initializer.
+    /// Returns [`OnceError::Poisoned`] if the cell is poisoned and `P` is
+    /// [`ErrorOnPoison`].
+    ///
+    /// # Panics
+    /// Panics if the cell is poisoned and `P` is [`PanicOnPoison`].
+    ///
+    /// [`ErrorOnPoison`]: crate::ErrorOnPoison
+    /// [`PanicOnPoison`]: crate::PanicOnPoison
+    pub fn try_get_or_init_nonblocking<F>(&self, f: F) -> Result<&T, OnceError<WouldBlock>>
+    where
+        F: FnOnce() -> T,
+    {
+        if let Some(value) = self.get() {
+            return Ok(value);
+        }
+
+        self.once
+            .try_call(|state| {
+                if !poison::should_run::<P>(state) {
+                    return Err(OnceError::Poisoned);
+                }
+                unsafe {
+                    self.init_value(f());
+                }
+                Ok(())
+            })
+            .map_err(OnceError::Failed)??;
+        Ok(unsafe { self.get_unchecked() })
+    }
+}
+
+impl<R, T, P> OnceLock<R, T, P>
+where
+    R: RawOnceSplit,
+    P: PoisonPolicy,
+{
+    /// Start initializing the cell, without handing over an initializer.
+    ///
+    /// Returns the value if the cell is already initialized, and otherwise an
+    /// [`InitGuard`] that holds the cell in the running state until it is completed with a
+    /// value. Other callers block in the meantime. Dropping the guard instead poisons the
+    /// cell.
+    ///
+    /// # Panics
+    /// Panics if the cell is poisoned and `P` is [`PanicOnPoison`], or if called while the
+    /// cell is being initialized from the same context.
+    ///
+    /// [`PanicOnPoison`]: crate::PanicOnPoison
+    pub fn begin_init(&self) -> BeginInit<'_, R, T, P> {
+        unwrap_once(self.try_begin_init())
+    }
+
+    /// Like [`OnceLock::begin_init`], but reports reentrancy instead of panicking.
+    ///
+    /// # Errors
+    /// Returns [`OnceError::Reentrant`] if called while the cell is being initialized from
+    /// the same context, and [`OnceError::Poisoned`] if the cell is poisoned and `P` is
+    /// [`ErrorOnPoison`].
+    ///
+    /// # Panics
+    /// Panics if the cell is poisoned and `P` is [`PanicOnPoison`].
+    ///
+    /// [`ErrorOnPoison`]: crate::ErrorOnPoison
+    /// [`PanicOnPoison`]: crate::PanicOnPoison
+    pub fn try_begin_init(&self) -> Result<BeginInit<'_, R, T, P>, OnceError> {
+        if let Some(value) = self.get() {
+            return Ok(BeginInit::Initialized(value));
+        }
+        if self.once.is_reentrant() {
+            return Err(OnceError::Reentrant);
+        }
+
+        let state = match self.once.acquire() {
+            Some(state) => state,
+            None => return Ok(BeginInit::Initialized(unsafe { self.get_unchecked() })),
+        };
+        let guard = InitGuard {
+            lock: self,
+            state,
+            _not_send: PhantomData,
+        };
+
+        if !poison::should_run::<P>(&guard.state) {
+            return Err(OnceError::Poisoned);
+        }
+        Ok(BeginInit::Uninitialized(guard))
+    }
+}
+
+impl<R, T, P> OnceLock<RawNotifyOnce<R>, T, P>
 where
     R: RawOnce,
+    P: PoisonPolicy,
+{
+    /// Wait until the cell has been initialized, without blocking the executor.
+    ///
+    /// The cell may be initialized by any other means, including from blocking code.
+    pub async fn wait_async(&self) -> &T {
+        if let Some(value) = self.get() {
+            return value;
+        }
+
+        WaitAsync {
+            lock: self,
+            waiter: self.once.waiter(),
+        }
+        .await
+    }
+}
+
+impl<R, T, P> Clone for OnceLock<R, T, P>
+where
+    R: RawOnce,
+    P: PoisonPolicy,
     T: Clone,
 {
     fn clone(&self) -> Self {
         if let Some(v) = self.get() {
-            Self::with_value(v.clone())
+            Self::with_value_and_policy(v.clone())
         } else {
-            Self::new()
+            Self::with_policy()
         }
     }
 }
 
-impl<R, T> Debug for OnceLock<R, T>
+impl<R, T, P> Debug for OnceLock<R, T, P>
 where
     R: RawOnce,
+    P: PoisonPolicy,
     T: Debug,
 {
     fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
@@ -151,25 +562,205 @@ where
     }
 }
 
-impl<R, T> Default for OnceLock<R, T>
+impl<R, T, P> Default for OnceLock<R, T, P>
 where
     R: RawOnce,
+    P: PoisonPolicy,
 {
     fn default() -> Self {
-        Self::new()
+        Self::with_policy()
     }
 }
 
-unsafe impl<R, T> Sync for OnceLock<R, T>
+impl<R, T, P> Drop for OnceLock<R, T, P>
 where
-    R: Send + Sync,
+    R: RawOnce,
+{
+    fn drop(&mut self) {
+        if self.once.is_completed() {
+            unsafe { self.value.get_mut().assume_init_drop() };
+        }
+    }
+}
+
+unsafe impl<R, T, P> Sync for OnceLock<R, T, P>
+where
+    R: RawOnce + Send + Sync,
     T: Send + Sync,
 {
 }
 
-unsafe impl<R, T> Send for OnceLock<R, T>
+unsafe impl<R, T, P> Send for OnceLock<R, T, P>
 where
-    R: Send,
+    R: RawOnce + Send,
     T: Send,
 {
 }
+
+/// A [`OnceLock`] under the brand `'id`, handed out by [`OnceLock::brand`].
+///
+/// Every call to [`OnceLock::brand`] makes up a new `'id`, so a [`OnceToken`] handed out
+/// for one cell cannot be passed to another, which fails to compile:
+///
+/// ```compile_fail
+/// # use sync_api::{OnceLock, RawOnce};
+/// fn mix<R: RawOnce>(a: &OnceLock<R, u32>, b: &OnceLock<R, u32>) {
+///     a.brand(|a| b.brand(|b| b.get_with_token(a.token().unwrap()).clone()));
+/// }
+/// ```
+pub struct BrandedOnceLock<'id, 'a, R, T, P = IgnorePoison>
+where
+    R: RawOnce,
+{
+    cell: &'a OnceLock<R, T, P>,
+    _brand: PhantomData<OnceToken<'id>>,
+}
+
+impl<'id, 'a, R, T, P> BrandedOnceLock<'id, 'a, R, T, P>
+where
+    R: RawOnce,
+    P: PoisonPolicy,
+{
+    /// A token proving that the cell has been initialized, if it has.
+    ///
+    /// Hand it to [`BrandedOnceLock::get_with_token`] to read the value without checking
+    /// again.
+    pub fn token(&self) -> Option<OnceToken<'id>> {
+        if self.cell.once.is_completed() {
+            Some(OnceToken {
+                _brand: PhantomData,
+            })
+        } else {
+            None
+        }
+    }
+
+    /// Get the value of the cell, known to be initialized, without an atomic load.
+    #[inline]
+    pub fn get_with_token(&self, _token: OnceToken<'id>) -> &'a T {
+        // Only this cell hands out tokens under its brand, and the cell cannot be reset
+        // while it is borrowed.
+        unsafe { self.cell.get_unchecked() }
+    }
+}
+
+impl<'a, R, T, P> Deref for BrandedOnceLock<'_, 'a, R, T, P>
+where
+    R: RawOnce,
+{
+    type Target = OnceLock<R, T, P>;
+
+    fn deref(&self) -> &Self::Target {
+        self.cell
+    }
+}
+
+impl<R, T, P> Clone for BrandedOnceLock<'_, '_, R, T, P>
+where
+    R: RawOnce,
+{
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<R, T, P> Copy for BrandedOnceLock<'_, '_, R, T, P> where R: RawOnce {}
+
+/// Proof that the [`BrandedOnceLock`] with the brand `'id` has been initialized.
+///
+/// `'id` is invariant, so the token cannot be passed off as proof for a cell under
+/// another brand. Checking it costs nothing at runtime.
+#[derive(Debug, Clone, Copy)]
+pub struct OnceToken<'id> {
+    _brand: PhantomData<fn(&'id ()) -> &'id ()>,
+}
+
+/// Resolves once the cell has been initialized.
+struct WaitAsync<'a, R, T, P>
+where
+    R: RawOnce,
+{
+    lock: &'a OnceLock<RawNotifyOnce<R>, T, P>,
+    waiter: Waiter<'a>,
+}
+
+impl<'a, R, T, P> Future for WaitAsync<'a, R, T, P>
+where
+    R: RawOnce,
+    P: PoisonPolicy,
+{
+    type Output = &'a T;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<&'a T> {
+        let lock = self.lock;
+        if let Some(value) = lock.get() {
+            return Poll::Ready(value);
+        }
+
+        // Register before looking again, so that completing in between is sure to wake us.
+        let waiter = unsafe { self.as_ref().map_unchecked(|wait| &wait.waiter) };
+        waiter.register(cx.waker());
+        match lock.get() {
+            Some(value) => Poll::Ready(value),
+            None => Poll::Pending,
+        }
+    }
+}
+
+/// The outcome of [`OnceLock::begin_init`].
+pub enum BeginInit<'a, R, T, P = IgnorePoison>
+where
+    R: RawOnceSplit,
+{
+    /// The cell already holds a value.
+    Initialized(&'a T),
+    /// The caller is now initializing the cell.
+    Uninitialized(InitGuard<'a, R, T, P>),
+}
+
+/// Holds a [`OnceLock`] in the running state until [`InitGuard::complete`] publishes its
+/// value.
+///
+/// Dropping the guard without completing it poisons the cell, just like an initializer
+/// that panics. The guard stays on the thread that created it, as the once remembers who
+/// is running it.
+pub struct InitGuard<'a, R, T, P = IgnorePoison>
+where
+    R: RawOnceSplit,
+{
+    lock: &'a OnceLock<R, T, P>,
+    state: OnceState,
+    _not_send: PhantomData<*const ()>,
+}
+
+impl<'a, R, T, P> InitGuard<'a, R, T, P>
+where
+    R: RawOnceSplit,
+    P: PoisonPolicy,
+{
+    /// The state the once was in when the initialization started.
+    pub fn state(&self) -> &OnceState {
+        &self.state
+    }
+
+    /// Store `value` in the cell and wake everyone waiting for it.
+    pub fn complete(self, value: T) -> &'a T {
+        let lock = self.lock;
+        mem::forget(self);
+
+        unsafe {
+            lock.init_value(value);
+            lock.once.release(true);
+            lock.get_unchecked()
+        }
+    }
+}
+
+impl<R, T, P> Drop for InitGuard<'_, R, T, P>
+where
+    R: RawOnceSplit,
+{
+    fn drop(&mut self) {
+        unsafe { self.lock.once.release(false) };
+    }
+}
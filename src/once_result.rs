@@ -0,0 +1,82 @@
+use core::fmt::Debug;
+
+use crate::{error::unwrap_once, OnceError, OnceLock, RawOnce};
+
+/// A cell that stores the outcome of a fallible initializer, whether it succeeded or not.
+///
+/// Unlike [`OnceLock::get_or_try_init`], an error is kept just like a value, so the
+/// initializer runs at most once and every caller sees the same result.
+pub struct OnceResult<R, T, E>
+where
+    R: RawOnce,
+{
+    cell: OnceLock<R, Result<T, E>>,
+}
+
+impl<R, T, E> OnceResult<R, T, E>
+where
+    R: RawOnce,
+{
+    pub const fn new() -> Self {
+        Self {
+            cell: OnceLock::new(),
+        }
+    }
+
+    pub fn get(&self) -> Option<Result<&T, &E>> {
+        self.cell.get().map(Result::as_ref)
+    }
+
+    pub fn get_mut(&mut self) -> Option<Result<&mut T, &mut E>> {
+        self.cell.get_mut().map(Result::as_mut)
+    }
+
+    pub fn into_inner(self) -> Option<Result<T, E>> {
+        self.cell.into_inner()
+    }
+
+    /// Get the result, running `f` to produce it if no one has yet.
+    ///
+    /// # Panics
+    /// Panics if called from within the cell's own initializer.
+    pub fn get_or_init<F>(&self, f: F) -> Result<&T, &E>
+    where
+        F: FnOnce() -> Result<T, E>,
+    {
+        unwrap_once(self.try_get_or_init(f))
+    }
+
+    /// Like [`OnceResult::get_or_init`], but reports reentrancy instead of panicking.
+    ///
+    /// # Errors
+    /// Returns [`OnceError::Reentrant`] if called from within the cell's own initializer.
+    pub fn try_get_or_init<F>(&self, f: F) -> Result<Result<&T, &E>, OnceError>
+    where
+        F: FnOnce() -> Result<T, E>,
+    {
+        let res = self.cell.get_or_try_init(|| Ok(f()));
+        res.map(Result::as_ref)
+    }
+}
+
+impl<R, T, E> Debug for OnceResult<R, T, E>
+where
+    R: RawOnce,
+    T: Debug,
+    E: Debug,
+{
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("OnceResult")
+            .field("result", &self.get())
+            .finish()
+    }
+}
+
+impl<R, T, E> Default for OnceResult<R, T, E>
+where
+    R: RawOnce,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,266 @@
+//! A [`RawOnce`] implementation that parks waiting threads using [`parking_lot_core`].
+//!
+//! The once is a state byte and the thread running the initializer. Waiters are kept in
+//! `parking_lot_core`'s global hash table, keyed by the address of the once.
+
+use core::{mem, sync::atomic::Ordering};
+use libstd::time::Instant;
+
+use parking_lot_core::{park, unpark_all, ParkResult, UnparkToken, DEFAULT_PARK_TOKEN};
+
+use crate::{
+    context::{Owner, StdThread},
+    into_ok,
+    limit::{Limit, NonBlocking, Unlimited, Until},
+    state::{AtomicState, State},
+    Deadline, OnceState, RawOnce, RawOnceNonBlocking, RawOnceSplit, RawOnceTimed, RawOnceWait,
+    Timeout, WouldBlock,
+};
+
+pub type Once<P = crate::PanicOnPoison> = crate::Once<RawPlOnce, P>;
+pub type OnceLock<T, P = crate::IgnorePoison> = crate::OnceLock<RawPlOnce, T, P>;
+pub type OnceResult<T, E> = crate::OnceResult<RawPlOnce, T, E>;
+pub type LazyLock<T, F = fn() -> T> = crate::LazyLock<RawPlOnce, T, F>;
+pub type TryLazyLock<T, E, F, P = crate::RetryOnError> = crate::TryLazyLock<RawPlOnce, T, E, F, P>;
+pub type AsyncOnceLock<T> = crate::AsyncOnceLock<RawPlOnce, T>;
+pub type AsyncLazy<T, F> = crate::AsyncLazy<RawPlOnce, T, F>;
+
+/// Handed to parked threads when the initializer completed successfully.
+const TOKEN_COMPLETE: UnparkToken = UnparkToken(1);
+/// Handed to parked threads when the initializer panicked or failed.
+const TOKEN_POISONED: UnparkToken = UnparkToken(2);
+
+pub struct RawPlOnce {
+    state: AtomicState,
+    owner: Owner<StdThread>,
+}
+
+impl RawPlOnce {
+    #[inline(never)]
+    fn acquire<L>(&self, limit: &L) -> Result<Option<OnceState>, L::Error>
+    where
+        L: Limit,
+    {
+        loop {
+            let state = self.state.load(Ordering::Acquire);
+
+            let once_state = match state {
+                State::Running => {
+                    let timeout = park_timeout(limit)?;
+                    let result = unsafe {
+                        park(
+                            key(&self.state),
+                            || self.state.load(Ordering::Acquire) == State::Running,
+                            || {},
+                            |_, _| {},
+                            DEFAULT_PARK_TOKEN,
+                            timeout,
+                        )
+                    };
+
+                    // The thread that unparked us stored the final state before doing
+                    // so, and parking synchronizes with unparking, so there is no need
+                    // to reload the state when we are told the once completed.
+                    match result {
+                        ParkResult::Unparked(TOKEN_COMPLETE) => return Ok(None),
+                        _ => continue,
+                    }
+                }
+                State::Complete => return Ok(None),
+                State::Incomplete => OnceState::new(),
+                State::Poisoned => OnceState::poisoned(),
+            };
+
+            if self
+                .state
+                .compare_exchange(state, State::Running, Ordering::AcqRel, Ordering::Relaxed)
+                .is_ok()
+            {
+                return Ok(Some(once_state));
+            }
+        }
+    }
+
+    fn call_limited<F, E, L>(&self, f: F, limit: &L) -> Result<Result<(), E>, L::Error>
+    where
+        F: FnOnce(&OnceState) -> Result<(), E>,
+        L: Limit,
+    {
+        let once_state = match self.acquire(limit)? {
+            Some(once_state) => once_state,
+            None => return Ok(Ok(())),
+        };
+
+        let guard = Guard { state: &self.state };
+
+        let entered = self.owner.enter();
+        let res = f(&once_state);
+        mem::drop(entered);
+
+        if let Err(err) = res {
+            return Ok(Err(err));
+        }
+        self.finish_init(guard);
+        Ok(Ok(()))
+    }
+
+    fn finish_init(&self, guard: Guard<'_>) {
+        mem::forget(guard);
+        self.state.store(State::Complete, Ordering::Release);
+
+        unsafe { unpark_all(key(&self.state), TOKEN_COMPLETE) };
+    }
+
+    fn wait_limited<L>(&self, ignore_poisoning: bool, limit: &L) -> Result<bool, L::Error>
+    where
+        L: Limit,
+    {
+        let should_park = || match self.state.load(Ordering::Acquire) {
+            State::Complete => false,
+            State::Poisoned => ignore_poisoning,
+            State::Incomplete | State::Running => true,
+        };
+
+        loop {
+            match self.state.load(Ordering::Acquire) {
+                State::Complete => return Ok(true),
+                State::Poisoned if !ignore_poisoning => return Ok(false),
+                _ => {}
+            }
+
+            let timeout = park_timeout(limit)?;
+            let result = unsafe {
+                park(
+                    key(&self.state),
+                    should_park,
+                    || {},
+                    |_, _| {},
+                    DEFAULT_PARK_TOKEN,
+                    timeout,
+                )
+            };
+            if result == ParkResult::Unparked(TOKEN_COMPLETE) {
+                return Ok(true);
+            }
+        }
+    }
+}
+
+unsafe impl RawOnce for RawPlOnce {
+    #[allow(clippy::declare_interior_mutable_const)]
+    const COMPLETE: Self = RawPlOnce {
+        state: AtomicState::new(State::Complete),
+        owner: Owner::new(),
+    };
+    #[allow(clippy::declare_interior_mutable_const)]
+    const INCOMPLETE: Self = RawPlOnce {
+        state: AtomicState::new(State::Incomplete),
+        owner: Owner::new(),
+    };
+
+    #[inline]
+    fn is_completed(&self) -> bool {
+        self.state.load(Ordering::Acquire) == State::Complete
+    }
+
+    fn is_poisoned(&self) -> bool {
+        self.state.load(Ordering::Acquire) == State::Poisoned
+    }
+
+    fn is_reentrant(&self) -> bool {
+        self.owner.is_current()
+    }
+
+    #[cold]
+    fn call_dyn(&self, f: &mut dyn FnMut(&OnceState) -> bool) {
+        // A failed initializer poisons the once, there is nothing else to report.
+        let _ = into_ok(self.call_limited(|state| f(state).then_some(()).ok_or(()), &Unlimited));
+    }
+}
+
+unsafe impl RawOnceWait for RawPlOnce {
+    fn wait(&self, ignore_poisoning: bool) -> bool {
+        into_ok(self.wait_limited(ignore_poisoning, &Unlimited))
+    }
+}
+
+unsafe impl RawOnceTimed for RawPlOnce {
+    #[cold]
+    fn call_until<F, E, D>(&self, f: F, deadline: &D) -> Result<Result<(), E>, Timeout>
+    where
+        F: FnOnce(&OnceState) -> Result<(), E>,
+        D: Deadline,
+    {
+        self.call_limited(f, &Until(deadline))
+    }
+
+    fn wait_until<D>(&self, ignore_poisoning: bool, deadline: &D) -> Result<bool, Timeout>
+    where
+        D: Deadline,
+    {
+        self.wait_limited(ignore_poisoning, &Until(deadline))
+    }
+}
+
+unsafe impl RawOnceNonBlocking for RawPlOnce {
+    #[cold]
+    fn try_call<F, E>(&self, f: F) -> Result<Result<(), E>, WouldBlock>
+    where
+        F: FnOnce(&OnceState) -> Result<(), E>,
+    {
+        self.call_limited(f, &NonBlocking)
+    }
+}
+
+unsafe impl RawOnceSplit for RawPlOnce {
+    fn acquire(&self) -> Option<OnceState> {
+        let once_state = into_ok(self.acquire(&Unlimited))?;
+        mem::forget(self.owner.enter());
+        Some(once_state)
+    }
+
+    unsafe fn release(&self, complete: bool) {
+        self.owner.leave();
+        let guard = Guard { state: &self.state };
+        if complete {
+            self.finish_init(guard);
+        }
+    }
+}
+
+/// Poisons the once and wakes every waiter if the initializer panics or fails.
+///
+/// All waiters are woken rather than just one, so that each of them can decide for itself
+/// whether to take over the initialization.
+struct Guard<'a> {
+    state: &'a AtomicState,
+}
+
+impl<'a> Drop for Guard<'a> {
+    fn drop(&mut self) {
+        self.state.store(State::Poisoned, Ordering::Release);
+        unsafe { unpark_all(key(self.state), TOKEN_POISONED) };
+    }
+}
+
+fn key(state: *const AtomicState) -> usize {
+    state as usize
+}
+
+/// The instant to stop parking at, if there is one.
+fn park_timeout<L>(limit: &L) -> Result<Option<Instant>, L::Error>
+where
+    L: Limit,
+{
+    Ok(limit
+        .remaining()?
+        .and_then(|remaining| Instant::now().checked_add(remaining)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RawPlOnce;
+    use crate::testing;
+
+    testing::backend_tests!(RawPlOnce, [split, wait, timed, nonblocking]);
+}
@@ -0,0 +1,66 @@
+use crate::{error::poisoned, OnceState};
+
+/// What [`Once`](crate::Once) and [`OnceLock`](crate::OnceLock) do when an earlier
+/// initializer panicked or failed, leaving them poisoned.
+///
+/// The policy is a type parameter, so each once picks its own without having to use the
+/// `_force` methods everywhere.
+pub trait PoisonPolicy {
+    /// Called before running an initializer on a poisoned once.
+    ///
+    /// Returns whether to run the initializer anyway. If not, the operation reports
+    /// [`OnceError::Poisoned`](crate::OnceError::Poisoned), or panics if it has no way to
+    /// report it.
+    fn run_poisoned(state: &OnceState) -> bool;
+}
+
+/// Panic when poisoned. This is the default for [`Once`](crate::Once).
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PanicOnPoison;
+
+impl PoisonPolicy for PanicOnPoison {
+    fn run_poisoned(state: &OnceState) -> bool {
+        poisoned(state)
+    }
+}
+
+/// Ignore poisoning and run the next initializer as if nothing happened. This is the
+/// default for [`OnceLock`](crate::OnceLock).
+#[derive(Debug, Clone, Copy, Default)]
+pub struct IgnorePoison;
+
+impl PoisonPolicy for IgnorePoison {
+    fn run_poisoned(_state: &OnceState) -> bool {
+        true
+    }
+}
+
+/// Report poisoning as [`OnceError::Poisoned`](crate::OnceError::Poisoned) instead of
+/// running the initializer.
+///
+/// Together with the `try_` methods, this leaves no path on which the types of this crate
+/// panic by themselves.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ErrorOnPoison;
+
+impl PoisonPolicy for ErrorOnPoison {
+    fn run_poisoned(_state: &OnceState) -> bool {
+        false
+    }
+}
+
+/// Whether an initializer handed `state` should run under the policy `P`.
+pub(crate) fn should_run<P>(state: &OnceState) -> bool
+where
+    P: PoisonPolicy,
+{
+    if !state.is_poisoned() {
+        return true;
+    }
+
+    // Marked before asking, as the policy may panic instead of answering.
+    state.set_skipped(true);
+    let run = P::run_poisoned(state);
+    state.set_skipped(!run);
+    run
+}
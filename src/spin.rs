@@ -0,0 +1,239 @@
+//! A [`RawOnce`] implementation that busy-waits while another thread is running the
+//! initializer.
+//!
+//! Spinning needs no operating system support, so this backend works in `no_std`
+//! environments. It is best suited to initializers that are short and rarely contended.
+
+use core::{mem, sync::atomic::Ordering};
+
+use crate::{
+    context::{Context, Owner},
+    into_ok,
+    limit::{Limit, NonBlocking, Unlimited, Until},
+    state::{AtomicState, State},
+    Deadline, OnceState, RawOnce, RawOnceNonBlocking, RawOnceSplit, RawOnceTimed, RawOnceWait,
+    Timeout, WouldBlock,
+};
+
+pub type Once<C, P = crate::PanicOnPoison> = crate::Once<RawSpinOnce<C>, P>;
+pub type OnceLock<C, T, P = crate::IgnorePoison> = crate::OnceLock<RawSpinOnce<C>, T, P>;
+pub type OnceResult<C, T, E> = crate::OnceResult<RawSpinOnce<C>, T, E>;
+pub type LazyLock<C, T, F = fn() -> T> = crate::LazyLock<RawSpinOnce<C>, T, F>;
+pub type TryLazyLock<C, T, E, F, P = crate::RetryOnError> =
+    crate::TryLazyLock<RawSpinOnce<C>, T, E, F, P>;
+pub type AsyncOnceLock<C, T> = crate::AsyncOnceLock<RawSpinOnce<C>, T>;
+pub type AsyncLazy<C, T, F> = crate::AsyncLazy<RawSpinOnce<C>, T, F>;
+
+/// A spinning once.
+///
+/// `C` identifies the context running the initializer, so that reentrant calls can be
+/// detected instead of spinning forever. It has no default, as none is right for every
+/// target: with [`Unidentified`](crate::Unidentified), an initializer that calls back into
+/// its own once spins until its deadline, or forever without one.
+pub struct RawSpinOnce<C>
+where
+    C: Context,
+{
+    state: AtomicState,
+    owner: Owner<C>,
+}
+
+impl<C> RawSpinOnce<C>
+where
+    C: Context,
+{
+    fn try_acquire<L>(&self, limit: &L) -> Result<Option<OnceState>, L::Error>
+    where
+        L: Limit,
+    {
+        loop {
+            let state = self.state.load(Ordering::Acquire);
+
+            let once_state = match state {
+                State::Running => {
+                    self.state.spin_while_running(limit)?;
+                    continue;
+                }
+                State::Complete => return Ok(None),
+                State::Incomplete => OnceState::new(),
+                State::Poisoned => OnceState::poisoned(),
+            };
+
+            if self
+                .state
+                .compare_exchange(state, State::Running, Ordering::AcqRel, Ordering::Relaxed)
+                .is_ok()
+            {
+                return Ok(Some(once_state));
+            }
+        }
+    }
+
+    fn finish_init(&self, guard: Guard<'_>) {
+        mem::forget(guard);
+        self.state.store(State::Complete, Ordering::Release);
+    }
+
+    fn call_limited<F, E, L>(&self, f: F, limit: &L) -> Result<Result<(), E>, L::Error>
+    where
+        F: FnOnce(&OnceState) -> Result<(), E>,
+        L: Limit,
+    {
+        let once_state = match self.try_acquire(limit)? {
+            Some(v) => v,
+            None => return Ok(Ok(())),
+        };
+
+        let guard = Guard { state: &self.state };
+
+        let entered = self.owner.enter();
+        let res = f(&once_state);
+        mem::drop(entered);
+
+        if let Err(err) = res {
+            return Ok(Err(err));
+        }
+        self.finish_init(guard);
+        Ok(Ok(()))
+    }
+}
+
+unsafe impl<C> RawOnce for RawSpinOnce<C>
+where
+    C: Context,
+{
+    #[allow(clippy::declare_interior_mutable_const)]
+    const COMPLETE: Self = Self {
+        state: AtomicState::new(State::Complete),
+        owner: Owner::new(),
+    };
+    #[allow(clippy::declare_interior_mutable_const)]
+    const INCOMPLETE: Self = Self {
+        state: AtomicState::new(State::Incomplete),
+        owner: Owner::new(),
+    };
+
+    #[inline]
+    fn is_completed(&self) -> bool {
+        self.state.load(Ordering::Acquire) == State::Complete
+    }
+
+    fn is_poisoned(&self) -> bool {
+        self.state.load(Ordering::Acquire) == State::Poisoned
+    }
+
+    fn is_reentrant(&self) -> bool {
+        self.owner.is_current()
+    }
+
+    #[cold]
+    fn call_dyn(&self, f: &mut dyn FnMut(&OnceState) -> bool) {
+        // A failed initializer poisons the once, there is nothing else to report.
+        let _ = into_ok(self.call_limited(|state| f(state).then_some(()).ok_or(()), &Unlimited));
+    }
+}
+
+unsafe impl<C> RawOnceWait for RawSpinOnce<C>
+where
+    C: Context,
+{
+    fn wait(&self, ignore_poisoning: bool) -> bool {
+        into_ok(self.state.spin_wait(ignore_poisoning, &Unlimited))
+    }
+}
+
+unsafe impl<C> RawOnceTimed for RawSpinOnce<C>
+where
+    C: Context,
+{
+    #[cold]
+    fn call_until<F, E, D>(&self, f: F, deadline: &D) -> Result<Result<(), E>, Timeout>
+    where
+        F: FnOnce(&OnceState) -> Result<(), E>,
+        D: Deadline,
+    {
+        self.call_limited(f, &Until(deadline))
+    }
+
+    fn wait_until<D>(&self, ignore_poisoning: bool, deadline: &D) -> Result<bool, Timeout>
+    where
+        D: Deadline,
+    {
+        self.state.spin_wait(ignore_poisoning, &Until(deadline))
+    }
+}
+
+unsafe impl<C> RawOnceNonBlocking for RawSpinOnce<C>
+where
+    C: Context,
+{
+    #[cold]
+    fn try_call<F, E>(&self, f: F) -> Result<Result<(), E>, WouldBlock>
+    where
+        F: FnOnce(&OnceState) -> Result<(), E>,
+    {
+        self.call_limited(f, &NonBlocking)
+    }
+}
+
+unsafe impl<C> RawOnceSplit for RawSpinOnce<C>
+where
+    C: Context,
+{
+    fn acquire(&self) -> Option<OnceState> {
+        let once_state = into_ok(self.try_acquire(&Unlimited))?;
+        mem::forget(self.owner.enter());
+        Some(once_state)
+    }
+
+    unsafe fn release(&self, complete: bool) {
+        self.owner.leave();
+        let guard = Guard { state: &self.state };
+        if complete {
+            self.finish_init(guard);
+        }
+    }
+}
+
+/// Poisons the once if the initializer panics or fails.
+struct Guard<'a> {
+    state: &'a AtomicState,
+}
+
+impl<'a> Drop for Guard<'a> {
+    fn drop(&mut self) {
+        self.state.store(State::Poisoned, Ordering::Release);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::mem;
+
+    use super::RawSpinOnce;
+    use crate::{
+        context::{Owner, StdThread},
+        state::AtomicState,
+        testing, Unidentified,
+    };
+
+    testing::backend_tests!(
+        RawSpinOnce<Unidentified>,
+        identified = RawSpinOnce<StdThread>,
+        [split, wait, timed, nonblocking]
+    );
+
+    #[test]
+    fn unidentified_reentrancy_spins() {
+        testing::unidentified_reentrancy_spins::<RawSpinOnce<Unidentified>>();
+    }
+
+    #[test]
+    fn unidentified_owner_takes_no_space() {
+        assert_eq!(mem::size_of::<Owner<Unidentified>>(), 0);
+        assert_eq!(
+            mem::size_of::<RawSpinOnce<Unidentified>>(),
+            mem::size_of::<AtomicState>()
+        );
+    }
+}
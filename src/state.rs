@@ -0,0 +1,102 @@
+#[cfg(any(feature = "spin", feature = "critical-section"))]
+use core::hint;
+use core::{
+    hint::unreachable_unchecked,
+    sync::atomic::{AtomicU8, Ordering},
+};
+
+#[cfg(any(feature = "spin", feature = "critical-section"))]
+use crate::limit::Limit;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum State {
+    Incomplete,
+    Running,
+    Complete,
+    Poisoned,
+}
+
+impl State {
+    #[inline]
+    unsafe fn from_u8(byte: u8) -> Self {
+        use State::*;
+
+        match byte {
+            0 => Incomplete,
+            1 => Running,
+            2 => Complete,
+            3 => Poisoned,
+            _ => unsafe { unreachable_unchecked() },
+        }
+    }
+}
+
+/// The four-state machine shared by the atomic-flag based backends.
+pub(crate) struct AtomicState(AtomicU8);
+
+impl AtomicState {
+    #[inline]
+    pub const fn new(state: State) -> Self {
+        Self(AtomicU8::new(state as u8))
+    }
+
+    #[inline]
+    pub fn compare_exchange(
+        &self,
+        current: State,
+        new: State,
+        success: Ordering,
+        failure: Ordering,
+    ) -> Result<State, State> {
+        match self
+            .0
+            .compare_exchange(current as u8, new as u8, success, failure)
+        {
+            Ok(v) => unsafe { Ok(State::from_u8(v)) },
+            Err(v) => unsafe { Err(State::from_u8(v)) },
+        }
+    }
+
+    #[inline]
+    pub fn load(&self, order: Ordering) -> State {
+        unsafe { State::from_u8(self.0.load(order)) }
+    }
+
+    /// Busy-wait until the state is complete, or poisoned unless `ignore_poisoning` is set.
+    ///
+    /// Returns whether the state is complete.
+    #[cfg(any(feature = "spin", feature = "critical-section"))]
+    pub fn spin_wait<L>(&self, ignore_poisoning: bool, limit: &L) -> Result<bool, L::Error>
+    where
+        L: Limit,
+    {
+        loop {
+            match self.load(Ordering::Acquire) {
+                State::Complete => return Ok(true),
+                State::Poisoned if !ignore_poisoning => return Ok(false),
+                _ => {
+                    limit.remaining()?;
+                    hint::spin_loop();
+                }
+            }
+        }
+    }
+
+    /// Busy-wait until the state is no longer running.
+    #[cfg(any(feature = "spin", feature = "critical-section"))]
+    pub fn spin_while_running<L>(&self, limit: &L) -> Result<(), L::Error>
+    where
+        L: Limit,
+    {
+        while self.load(Ordering::Acquire) == State::Running {
+            limit.remaining()?;
+            hint::spin_loop();
+        }
+        Ok(())
+    }
+
+    #[inline]
+    pub fn store(&self, value: State, order: Ordering) {
+        self.0.store(value as u8, order);
+    }
+}
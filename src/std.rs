@@ -0,0 +1,585 @@
+//! A [`RawOnce`] implementation that parks waiting threads using
+//! [`std::thread`](libstd::thread).
+//!
+//! Waiters are kept in an intrusive queue stored on their own stacks, so the once itself
+//! only holds the head of the queue and the thread running the initializer. Waiters with a
+//! deadline may leave before they are woken, so their queue nodes are shared with the
+//! queue on the heap instead, and taken off the queue again by the waiter that gives up.
+//!
+//! The aliases in this module wrap the once in a [`RawAttemptsOnce`], so that the panic of
+//! a poisoned [`Once`] says why it was poisoned. That wrapper has no two-phase
+//! initialization, so use [`RawStdOnce`] directly for
+//! [`OnceLock::begin_init`](crate::OnceLock::begin_init).
+
+use core::{cell::Cell, mem};
+use libstd::{
+    sync::{
+        atomic::{AtomicBool, AtomicPtr, Ordering},
+        Arc,
+    },
+    thread::{self, Thread},
+};
+
+use crate::{
+    context::{Owner, StdThread},
+    into_ok,
+    limit::{Limit, NonBlocking, Unlimited, Until},
+    Deadline, OnceState, RawAttemptsOnce, RawOnce, RawOnceNonBlocking, RawOnceSplit, RawOnceTimed,
+    RawOnceWait, Timeout, WouldBlock,
+};
+
+pub type Once<P = crate::PanicOnPoison> = crate::Once<RawAttemptsOnce<RawStdOnce>, P>;
+pub type OnceLock<T, P = crate::IgnorePoison> = crate::OnceLock<RawAttemptsOnce<RawStdOnce>, T, P>;
+pub type OnceResult<T, E> = crate::OnceResult<RawAttemptsOnce<RawStdOnce>, T, E>;
+pub type LazyLock<T, F = fn() -> T> = crate::LazyLock<RawAttemptsOnce<RawStdOnce>, T, F>;
+pub type TryLazyLock<T, E, F, P = crate::RetryOnError> =
+    crate::TryLazyLock<RawAttemptsOnce<RawStdOnce>, T, E, F, P>;
+pub type AsyncOnceLock<T> = crate::AsyncOnceLock<RawAttemptsOnce<RawStdOnce>, T>;
+pub type AsyncLazy<T, F> = crate::AsyncLazy<RawAttemptsOnce<RawStdOnce>, T, F>;
+
+pub struct RawStdOnce {
+    queue: AtomicPtr<Waiter>,
+    owner: Owner<StdThread>,
+}
+
+impl RawStdOnce {
+    fn call_limited<F, E, L>(&self, f: F, limit: &L) -> Result<Result<(), E>, L::Error>
+    where
+        F: FnOnce(&OnceState) -> Result<(), E>,
+        L: Limit,
+    {
+        let mut f = Some(f);
+        let mut err = None;
+
+        initialize_or_wait(
+            &self.queue,
+            &mut |once_state| {
+                let f = unsafe { f.take().unwrap_unchecked() };
+                let _entered = self.owner.enter();
+                match f(once_state) {
+                    Ok(_) => true,
+                    Err(e) => {
+                        err = Some(e);
+                        false
+                    }
+                }
+            },
+            limit,
+        )?;
+
+        match err {
+            Some(err) => Ok(Err(err)),
+            None => Ok(Ok(())),
+        }
+    }
+
+    fn wait_limited<L>(&self, ignore_poisoning: bool, limit: &L) -> Result<bool, L::Error>
+    where
+        L: Limit,
+    {
+        let mut curr_queue = self.queue.load(Ordering::Acquire);
+
+        loop {
+            match strict::addr(curr_queue) & STATE_MASK {
+                COMPLETE => return Ok(true),
+                POISONED if !ignore_poisoning => return Ok(false),
+                _ => curr_queue = wait(&self.queue, curr_queue, !ignore_poisoning, limit)?,
+            }
+        }
+    }
+}
+
+unsafe impl RawOnce for RawStdOnce {
+    #[allow(clippy::declare_interior_mutable_const)]
+    const COMPLETE: Self = Self {
+        queue: AtomicPtr::new(COMPLETE_PTR),
+        owner: Owner::new(),
+    };
+    #[allow(clippy::declare_interior_mutable_const)]
+    const INCOMPLETE: Self = Self {
+        queue: AtomicPtr::new(INCOMPLETE_PTR),
+        owner: Owner::new(),
+    };
+
+    #[inline]
+    fn is_completed(&self) -> bool {
+        self.queue.load(Ordering::Acquire) == COMPLETE_PTR
+    }
+
+    fn is_poisoned(&self) -> bool {
+        strict::addr(self.queue.load(Ordering::Acquire)) & STATE_MASK == POISONED
+    }
+
+    fn is_reentrant(&self) -> bool {
+        self.owner.is_current()
+    }
+
+    fn call_dyn(&self, f: &mut dyn FnMut(&OnceState) -> bool) {
+        into_ok(initialize_or_wait(
+            &self.queue,
+            &mut |once_state| {
+                let _entered = self.owner.enter();
+                f(once_state)
+            },
+            &Unlimited,
+        ))
+    }
+}
+
+impl Drop for RawStdOnce {
+    fn drop(&mut self) {
+        // Untimed waiters borrow the once, so only nodes left behind by waiters with a
+        // deadline can still be queued.
+        let mut waiter = strict::map_addr(*self.queue.get_mut(), |q| q & !STATE_MASK);
+        while !waiter.is_null() {
+            unsafe {
+                let next = (*waiter).next;
+                debug_assert!((*waiter).shared);
+                drop(Arc::from_raw(waiter));
+                waiter = next;
+            }
+        }
+    }
+}
+
+unsafe impl RawOnceWait for RawStdOnce {
+    fn wait(&self, ignore_poisoning: bool) -> bool {
+        into_ok(self.wait_limited(ignore_poisoning, &Unlimited))
+    }
+}
+
+unsafe impl RawOnceTimed for RawStdOnce {
+    #[inline]
+    fn call_until<F, E, D>(&self, f: F, deadline: &D) -> Result<Result<(), E>, Timeout>
+    where
+        F: FnOnce(&OnceState) -> Result<(), E>,
+        D: Deadline,
+    {
+        self.call_limited(f, &Until(deadline))
+    }
+
+    fn wait_until<D>(&self, ignore_poisoning: bool, deadline: &D) -> Result<bool, Timeout>
+    where
+        D: Deadline,
+    {
+        self.wait_limited(ignore_poisoning, &Until(deadline))
+    }
+}
+
+unsafe impl RawOnceNonBlocking for RawStdOnce {
+    #[inline]
+    fn try_call<F, E>(&self, f: F) -> Result<Result<(), E>, WouldBlock>
+    where
+        F: FnOnce(&OnceState) -> Result<(), E>,
+    {
+        self.call_limited(f, &NonBlocking)
+    }
+}
+
+unsafe impl RawOnceSplit for RawStdOnce {
+    fn acquire(&self) -> Option<OnceState> {
+        let once_state = into_ok(acquire(&self.queue, &Unlimited))?;
+        mem::forget(self.owner.enter());
+        Some(once_state)
+    }
+
+    unsafe fn release(&self, complete: bool) {
+        self.owner.leave();
+        mem::drop(Guard {
+            queue: &self.queue,
+            new_queue: if complete { COMPLETE_PTR } else { POISONED_PTR },
+        });
+    }
+}
+
+// Four states that a Once can be in, encoded into the lower bits of `queue` in
+// the Once structure.
+const INCOMPLETE: usize = 0x0;
+const RUNNING: usize = 0x1;
+const COMPLETE: usize = 0x2;
+const POISONED: usize = 0x3;
+const INCOMPLETE_PTR: *mut Waiter = INCOMPLETE as *mut Waiter;
+const COMPLETE_PTR: *mut Waiter = COMPLETE as *mut Waiter;
+const POISONED_PTR: *mut Waiter = POISONED as *mut Waiter;
+
+// Mask to learn about the state. All other bits are the queue of waiters, which
+// may be non-empty in any state except COMPLETE.
+const STATE_MASK: usize = 0x3;
+
+/// Representation of a node in the linked list of waiters.
+/// A waiters is stored on the stack of the waiting threads.
+#[repr(align(4))] // Ensure the two lower bits are free to use as state bits.
+struct Waiter {
+    thread: Cell<Option<Thread>>,
+    signaled: AtomicBool,
+    next: *mut Waiter,
+    /// Set if the node is an `Arc` that the queue holds a reference to.
+    shared: bool,
+}
+
+/// Drains and notifies the queue of waiters on drop.
+struct Guard<'a> {
+    queue: &'a AtomicPtr<Waiter>,
+    new_queue: *mut Waiter,
+}
+
+impl Drop for Guard<'_> {
+    fn drop(&mut self) {
+        let queue = self.queue.swap(self.new_queue, Ordering::AcqRel);
+
+        // Only the caller running the once creates a guard, so this cannot fail. It is not
+        // checked in release builds to keep panics out of the drop.
+        let state = strict::addr(queue) & STATE_MASK;
+        debug_assert_eq!(state, RUNNING);
+
+        unsafe { wake_all(queue) }
+    }
+}
+
+/// Signals every waiter in a queue that was taken off the once.
+///
+/// # Safety
+/// The caller must have taken `queue` off the once, so that no one else walks it.
+unsafe fn wake_all(queue: *mut Waiter) {
+    let mut waiter = strict::map_addr(queue, |q| q & !STATE_MASK);
+    while !waiter.is_null() {
+        let next = (*waiter).next;
+        let shared = (*waiter).shared;
+        // Every queued waiter has its thread set, and is only signaled once.
+        let thread = (*waiter).thread.take();
+        (*waiter).signaled.store(true, Ordering::Release);
+        if shared {
+            drop(Arc::from_raw(waiter));
+        }
+        waiter = next;
+        if let Some(thread) = thread {
+            thread.unpark();
+        }
+    }
+}
+
+// Corresponds to `std::sync::Once::call_inner`.
+//
+// Originally copied from std, but since modified to remove poisoning and to
+// support wait.
+//
+// Note: this is intentionally monomorphic in the initializer
+#[inline(never)]
+fn initialize_or_wait<L>(
+    queue: &AtomicPtr<Waiter>,
+    init: &mut dyn FnMut(&OnceState) -> bool,
+    limit: &L,
+) -> Result<(), L::Error>
+where
+    L: Limit,
+{
+    if let Some(once_state) = acquire(queue, limit)? {
+        let mut guard = Guard {
+            queue,
+            new_queue: POISONED_PTR,
+        };
+        if init(&once_state) {
+            guard.new_queue = COMPLETE_PTR;
+        }
+    }
+    Ok(())
+}
+
+/// Moves the once into the running state, waiting while another caller holds it.
+///
+/// Returns `None` if the once is complete. Otherwise the caller must publish the final
+/// state through a [`Guard`].
+fn acquire<L>(queue: &AtomicPtr<Waiter>, limit: &L) -> Result<Option<OnceState>, L::Error>
+where
+    L: Limit,
+{
+    let mut curr_queue = queue.load(Ordering::Acquire);
+
+    loop {
+        let curr_state = strict::addr(curr_queue) & STATE_MASK;
+        match curr_state {
+            COMPLETE => return Ok(None),
+            INCOMPLETE | POISONED => {
+                let exchange = queue.compare_exchange(
+                    curr_queue,
+                    strict::map_addr(curr_queue, |q| (q & !STATE_MASK) | RUNNING),
+                    Ordering::Acquire,
+                    Ordering::Acquire,
+                );
+                if let Err(new_queue) = exchange {
+                    curr_queue = new_queue;
+                    continue;
+                }
+
+                let mut once_state = OnceState::new();
+                if curr_state == POISONED {
+                    once_state.poison();
+                }
+                return Ok(Some(once_state));
+            }
+            RUNNING => {
+                curr_queue = wait(queue, curr_queue, true, limit)?;
+            }
+            _ => debug_assert!(false),
+        }
+    }
+}
+
+/// Queues the current thread and parks it until the once leaves its current state.
+///
+/// Returns immediately if the once is complete, or poisoned and `return_on_poisoned` is
+/// set. Otherwise returns the state observed after being woken.
+fn wait<L>(
+    queue: &AtomicPtr<Waiter>,
+    mut curr_queue: *mut Waiter,
+    return_on_poisoned: bool,
+    limit: &L,
+) -> Result<*mut Waiter, L::Error>
+where
+    L: Limit,
+{
+    loop {
+        let curr_state = strict::addr(curr_queue) & STATE_MASK;
+        if curr_state == COMPLETE || (return_on_poisoned && curr_state == POISONED) {
+            return Ok(curr_queue);
+        }
+
+        let parked = match limit.remaining()? {
+            None => park(queue, curr_queue),
+            Some(_) => park_timeout(queue, curr_queue, limit)?,
+        };
+        match parked {
+            Ok(()) => return Ok(queue.load(Ordering::Acquire)),
+            Err(new_queue) => curr_queue = new_queue,
+        }
+    }
+}
+
+/// Pushes a node for the current thread onto the queue and parks until it is signaled.
+///
+/// Fails with the new queue if it changed before the node could be pushed.
+fn park(queue: &AtomicPtr<Waiter>, curr_queue: *mut Waiter) -> Result<(), *mut Waiter> {
+    let node = Waiter {
+        thread: Cell::new(Some(thread::current())),
+        signaled: AtomicBool::new(false),
+        next: strict::map_addr(curr_queue, |q| q & !STATE_MASK),
+        shared: false,
+    };
+    let me = &node as *const Waiter as *mut Waiter;
+
+    let curr_state = strict::addr(curr_queue) & STATE_MASK;
+    queue.compare_exchange(
+        curr_queue,
+        strict::map_addr(me, |q| q | curr_state),
+        Ordering::Release,
+        Ordering::Acquire,
+    )?;
+
+    while !node.signaled.load(Ordering::Acquire) {
+        thread::park();
+    }
+    Ok(())
+}
+
+/// Like [`park`], but stops waiting once `limit` runs out.
+///
+/// The node may still be queued when we leave, so it lives in an `Arc` that the queue
+/// holds a reference to until it is drained.
+fn park_timeout<L>(
+    queue: &AtomicPtr<Waiter>,
+    curr_queue: *mut Waiter,
+    limit: &L,
+) -> Result<Result<(), *mut Waiter>, L::Error>
+where
+    L: Limit,
+{
+    // The node is only ever shared through raw pointers, and only the thread draining
+    // the queue touches its `thread` once it has been pushed.
+    #[allow(clippy::arc_with_non_send_sync)]
+    let node = Arc::new(Waiter {
+        thread: Cell::new(Some(thread::current())),
+        signaled: AtomicBool::new(false),
+        next: strict::map_addr(curr_queue, |q| q & !STATE_MASK),
+        shared: true,
+    });
+    let me = Arc::as_ptr(&node) as *mut Waiter;
+
+    let curr_state = strict::addr(curr_queue) & STATE_MASK;
+    let exchange = queue.compare_exchange(
+        curr_queue,
+        strict::map_addr(me, |q| q | curr_state),
+        Ordering::Release,
+        Ordering::Acquire,
+    );
+    if let Err(new_queue) = exchange {
+        return Ok(Err(new_queue));
+    }
+    mem::forget(Arc::clone(&node));
+
+    while !node.signaled.load(Ordering::Acquire) {
+        match limit.remaining() {
+            Ok(Some(timeout)) => thread::park_timeout(timeout),
+            Ok(None) => thread::park(),
+            Err(err) => {
+                leave(queue, &node);
+                return Err(err);
+            }
+        }
+    }
+    Ok(Ok(()))
+}
+
+/// Takes the node of a waiter that gave up off the queue, so that waiters polling with a
+/// deadline do not grow it.
+///
+/// Only the head of the queue can be unlinked safely. If another waiter has queued up
+/// since, the whole queue is taken and woken instead, and its waiters queue up again.
+fn leave(queue: &AtomicPtr<Waiter>, node: &Waiter) {
+    let me = node as *const Waiter as *mut Waiter;
+    let mut curr_queue = queue.load(Ordering::Acquire);
+
+    loop {
+        // A signaled node has been taken off by whoever signaled it.
+        if node.signaled.load(Ordering::Acquire) {
+            return;
+        }
+
+        let curr_state = strict::addr(curr_queue) & STATE_MASK;
+        let head = strict::map_addr(curr_queue, |q| q & !STATE_MASK);
+        let new_queue = if head == me {
+            // The nodes behind ours cannot have left while ours is at the head.
+            strict::map_addr(node.next, |q| q | curr_state)
+        } else if head.is_null() {
+            return;
+        } else {
+            curr_state as *mut Waiter
+        };
+
+        match queue.compare_exchange(curr_queue, new_queue, Ordering::AcqRel, Ordering::Acquire) {
+            Ok(_) if head == me => {
+                // Release the reference the queue held.
+                unsafe { drop(Arc::from_raw(me)) };
+                return;
+            }
+            Ok(_) => {
+                unsafe { wake_all(curr_queue) };
+                return;
+            }
+            Err(new_queue) => curr_queue = new_queue,
+        }
+    }
+}
+
+// Polyfill of strict provenance from https://crates.io/crates/sptr.
+//
+// Use free-standing function rather than a trait to keep things simple and
+// avoid any potential conflicts with future stabile std API.
+mod strict {
+    #[must_use]
+    #[inline]
+    #[allow(clippy::transmutes_expressible_as_ptr_casts)]
+    pub(crate) fn addr<T>(ptr: *mut T) -> usize
+    where
+        T: Sized,
+    {
+        // FIXME(strict_provenance_magic): I am magic and should be a compiler intrinsic.
+        // SAFETY: Pointer-to-integer transmutes are valid (if you are okay with losing the
+        // provenance).
+        unsafe { core::mem::transmute(ptr) }
+    }
+
+    #[must_use]
+    #[inline]
+    pub(crate) fn with_addr<T>(ptr: *mut T, addr: usize) -> *mut T
+    where
+        T: Sized,
+    {
+        // FIXME(strict_provenance_magic): I am magic and should be a compiler intrinsic.
+        //
+        // In the mean-time, this operation is defined to be "as if" it was
+        // a wrapping_offset, so we can emulate it as such. This should properly
+        // restore pointer provenance even under today's compiler.
+        let self_addr = self::addr(ptr) as isize;
+        let dest_addr = addr as isize;
+        let offset = dest_addr.wrapping_sub(self_addr);
+
+        // This is the canonical desugarring of this operation,
+        // but `pointer::cast` was only stabilized in 1.38.
+        // self.cast::<u8>().wrapping_offset(offset).cast::<T>()
+        (ptr as *mut u8).wrapping_offset(offset) as *mut T
+    }
+
+    #[must_use]
+    #[inline]
+    pub(crate) fn map_addr<T>(ptr: *mut T, f: impl FnOnce(usize) -> usize) -> *mut T
+    where
+        T: Sized,
+    {
+        self::with_addr(ptr, f(addr(ptr)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use libstd::{
+        panic::{self, AssertUnwindSafe},
+        string::String,
+        sync::atomic::Ordering,
+        thread,
+        time::{Duration, Instant},
+    };
+
+    use super::{RawStdOnce, INCOMPLETE_PTR, RUNNING, STATE_MASK};
+    use crate::{testing, ExclusiveCell, RawOnce, RawOnceSplit, RawOnceTimed, Timeout};
+
+    testing::backend_tests!(RawStdOnce, [split, wait, timed, nonblocking]);
+
+    #[test]
+    fn exclusive_cell_in_static() {
+        static CELL: ExclusiveCell<RawStdOnce, u32> = ExclusiveCell::new();
+
+        let value: &'static mut u32 = CELL.init(|| 1).unwrap();
+        *value += 1;
+        assert_eq!(*value, 2);
+        assert!(CELL.init(|| unreachable!()).is_none());
+    }
+
+    #[test]
+    fn poisoned_once_panics_with_the_first_message() {
+        let once = super::Once::new();
+        let res = panic::catch_unwind(AssertUnwindSafe(|| once.call_once(|| panic!("boom"))));
+        assert!(res.is_err());
+
+        let payload = panic::catch_unwind(AssertUnwindSafe(|| once.call_once(|| {}))).unwrap_err();
+        let message = payload.downcast_ref::<String>().unwrap();
+        assert!(message.contains("boom"), "{message}");
+    }
+
+    #[test]
+    fn timed_out_waiters_leave_the_queue() {
+        let once = RawStdOnce::INCOMPLETE;
+        for _ in 0..100 {
+            let soon = Instant::now() + Duration::from_micros(10);
+            assert_eq!(once.wait_until(false, &soon), Err(Timeout));
+        }
+        assert_eq!(once.queue.load(Ordering::Relaxed), INCOMPLETE_PTR);
+
+        assert!(once.acquire().is_some());
+        thread::scope(|s| {
+            for _ in 0..4 {
+                s.spawn(|| {
+                    for _ in 0..100 {
+                        let soon = Instant::now() + Duration::from_micros(10);
+                        assert_eq!(once.wait_until(false, &soon), Err(Timeout));
+                    }
+                });
+            }
+        });
+        assert_eq!(once.queue.load(Ordering::Relaxed) as usize & !STATE_MASK, 0);
+        assert_eq!(
+            once.queue.load(Ordering::Relaxed) as usize & STATE_MASK,
+            RUNNING
+        );
+        unsafe { once.release(true) };
+    }
+}
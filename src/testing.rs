@@ -0,0 +1,1003 @@
+//! Behaviour every bundled [`RawOnce`] backend is expected to share.
+
+use libstd::{
+    boxed::Box,
+    cell::Cell,
+    future::{self, Future},
+    mem,
+    panic::{self, AssertUnwindSafe},
+    pin::{pin, Pin},
+    string::String,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Barrier, Mutex,
+    },
+    task::{self, Poll, Wake, Waker},
+    thread::{self, Thread},
+    time::{Duration, Instant},
+    vec::Vec,
+};
+
+#[cfg(feature = "std")]
+use crate::RawAttemptsOnce;
+use crate::{
+    AsyncLazy, AsyncOnceLock, BeginInit, CacheError, ErrorOnPoison, ExclusiveCell, IgnorePoison,
+    LazyLock, Once, OnceError, OnceLock, OnceResult, RawNotifyOnce, RawOnce, RawOnceNonBlocking,
+    RawOnceSplit, RawOnceTimed, RawOnceWait, RetryOnError, Timeout, TryInitError, TryLazyLock,
+    WouldBlock,
+};
+
+const THREADS: usize = 8;
+
+/// Generates a `#[test]` for every helper in this module that `$raw` supports.
+///
+/// Reentrancy can only be detected by a backend whose context identifies the running
+/// thread, so those tests run against `identified` when it is given. The bracketed list
+/// names the extension traits the backend implements beyond [`RawOnce`]: `split`, `wait`,
+/// `timed` and `nonblocking`, the last of which also requires `timed`.
+macro_rules! backend_tests {
+    (@tests $raw:ty; $($(#[$attr:meta])* $name:ident)*) => {
+        $(
+            #[test]
+            $(#[$attr])*
+            fn $name() {
+                $crate::testing::$name::<$raw>();
+            }
+        )*
+    };
+    (@split $raw:ty) => {
+        $crate::testing::backend_tests!(@tests $raw; begin_init_completes_or_poisons);
+    };
+    (@wait $raw:ty) => {
+        $crate::testing::backend_tests!(@tests $raw;
+            wait_blocks_until_initialized
+            wait_observes_poisoning
+        );
+    };
+    (@timed $raw:ty) => {
+        $crate::testing::backend_tests!(@tests $raw;
+            wait_timeout_expires
+            timeout_while_running
+        );
+    };
+    (@nonblocking $raw:ty) => {
+        $crate::testing::backend_tests!(@tests $raw;
+            nonblocking_would_block_while_running
+            try_apis_do_not_panic
+        );
+    };
+    ($raw:ty, [$($ext:ident),*]) => {
+        $crate::testing::backend_tests!($raw, identified = $raw, [$($ext),*]);
+    };
+    ($raw:ty, identified = $identified:ty, [$($ext:ident),*]) => {
+        $crate::testing::backend_tests!(@tests $raw;
+            races_to_a_single_value
+            with_value_is_initialized
+            tokens_prove_initialization
+            storage_drops_the_value_once
+            error_leaves_uninitialized
+            once_result_keeps_the_error
+            panic_poisons
+            poison_policies
+            #[cfg(feature = "std")]
+            poisoning_reports_attempts
+            lazy_panic_poisons
+            lazy_accessors_do_not_force
+            lazy_shares_one_slot
+            try_lazy_retries_on_error
+            try_lazy_caches_the_first_error
+            async_races_to_a_single_value
+            async_cancelled_init_is_taken_over
+            async_wake_can_drop_the_waiter
+            notify_wakes_async_waiters
+            notify_wake_can_drop_the_waiter
+        );
+        $crate::testing::backend_tests!(@tests $identified;
+            reentrancy_is_reported
+            exclusive_cell_hands_out_once
+        );
+        $($crate::testing::backend_tests!(@$ext $raw);)*
+    };
+}
+
+pub(crate) use backend_tests;
+
+pub(crate) fn races_to_a_single_value<R>()
+where
+    R: RawOnce + Send + Sync,
+{
+    let cell = OnceLock::<R, usize>::new();
+    let barrier = Barrier::new(THREADS);
+
+    let values: Vec<usize> = thread::scope(|s| {
+        let handles: Vec<_> = (0..THREADS)
+            .map(|i| {
+                let cell = &cell;
+                let barrier = &barrier;
+                s.spawn(move || {
+                    barrier.wait();
+                    *cell.get_or_init(|| {
+                        // Give the other threads time to start waiting on us.
+                        thread::sleep(Duration::from_millis(10));
+                        i
+                    })
+                })
+            })
+            .collect();
+
+        handles.into_iter().map(|h| h.join().unwrap()).collect()
+    });
+
+    let winner = values[0];
+    assert!(values.iter().all(|&v| v == winner));
+    assert_eq!(cell.get(), Some(&winner));
+}
+
+pub(crate) fn with_value_is_initialized<R>()
+where
+    R: RawOnce,
+{
+    let cell = OnceLock::<R, _>::with_value(String::from("value"));
+    assert_eq!(cell.get().map(String::as_str), Some("value"));
+    assert_eq!(cell.set(String::from("other")), Err(String::from("other")));
+
+    let once = Once::<R>::new();
+    assert!(!once.is_completed());
+    once.call_once(|| {});
+    assert!(once.is_completed());
+    once.call_once(|| unreachable!());
+}
+
+pub(crate) fn tokens_prove_initialization<R>()
+where
+    R: RawOnce,
+{
+    let cell = OnceLock::<R, u32>::new();
+    let value = cell.brand(|cell| {
+        assert!(cell.token().is_none());
+        cell.get_or_init(|| 1);
+        let token = cell.token().unwrap();
+        cell.get_with_token(token)
+    });
+    assert_eq!(value, &1);
+}
+
+pub(crate) fn storage_drops_the_value_once<R>()
+where
+    R: RawOnce,
+{
+    assert_eq!(
+        mem::size_of::<OnceLock<R, u64>>(),
+        mem::size_of::<(R, u64)>()
+    );
+
+    let value = Arc::new(());
+    let cell = OnceLock::<R, Arc<()>>::new();
+    assert!(cell.set(value.clone()).is_ok());
+    assert_eq!(Arc::strong_count(&value), 2);
+    drop(cell);
+    assert_eq!(Arc::strong_count(&value), 1);
+
+    let mut cell = OnceLock::<R, _>::with_value(value.clone());
+    assert!(cell.get_mut().is_some());
+    assert!(cell.take().is_some_and(|taken| Arc::ptr_eq(&taken, &value)));
+    assert!(cell.get_mut().is_none());
+    assert!(cell.take().is_none());
+    assert_eq!(Arc::strong_count(&value), 1);
+
+    assert!(cell.set(value.clone()).is_ok());
+    assert!(cell.into_inner().is_some());
+    assert_eq!(Arc::strong_count(&value), 1);
+
+    let mut cell = OnceLock::<R, Arc<()>, ErrorOnPoison>::with_policy();
+    let res = panic::catch_unwind(AssertUnwindSafe(|| cell.get_or_init(|| panic!("boom"))));
+    assert!(res.is_err());
+    assert!(cell.take().is_none());
+    assert!(cell.get_or_try_init(|| Ok::<_, ()>(value.clone())).is_ok());
+    assert!(cell.into_inner().is_some());
+    assert_eq!(Arc::strong_count(&value), 1);
+}
+
+pub(crate) fn error_leaves_uninitialized<R>()
+where
+    R: RawOnce,
+{
+    let cell = OnceLock::<R, u32>::new();
+    assert_eq!(
+        cell.get_or_try_init(|| Err("failed")),
+        Err(OnceError::Failed("failed"))
+    );
+    assert_eq!(cell.get(), None);
+    assert_eq!(cell.get_or_try_init(|| Ok::<_, ()>(1)), Ok(&1));
+    assert_eq!(cell.get_or_init(|| 2), &1);
+}
+
+pub(crate) fn once_result_keeps_the_error<R>()
+where
+    R: RawOnce + Send + Sync,
+{
+    let cell = OnceResult::<R, u32, &str>::new();
+    let calls = AtomicUsize::new(0);
+    let barrier = Barrier::new(THREADS);
+
+    thread::scope(|s| {
+        for _ in 0..THREADS {
+            s.spawn(|| {
+                barrier.wait();
+                let res = cell.get_or_init(|| {
+                    calls.fetch_add(1, Ordering::Relaxed);
+                    Err("failed")
+                });
+                assert_eq!(res, Err(&"failed"));
+            });
+        }
+    });
+
+    assert_eq!(calls.load(Ordering::Relaxed), 1);
+    assert_eq!(cell.get_or_init(|| Ok(1)), Err(&"failed"));
+    assert_eq!(cell.into_inner(), Some(Err("failed")));
+}
+
+pub(crate) fn reentrancy_is_reported<R>()
+where
+    R: RawOnce,
+{
+    let cell = OnceLock::<R, u32>::new();
+    let value = cell.get_or_init(|| {
+        assert_eq!(
+            cell.get_or_try_init(|| Ok::<_, ()>(2)),
+            Err(OnceError::Reentrant)
+        );
+        let res = panic::catch_unwind(AssertUnwindSafe(|| cell.get_or_init(|| 2)));
+        assert!(res.is_err());
+        assert_eq!(cell.try_set(2), Err(OnceError::Reentrant));
+        assert_eq!(cell.try_insert_checked(2), Err(OnceError::Reentrant));
+        1
+    });
+    assert_eq!(value, &1);
+
+    let once = Once::<R>::new();
+    once.call_once(|| {
+        assert_eq!(
+            once.try_call_once(|| Ok::<_, ()>(())),
+            Err(OnceError::Reentrant)
+        );
+        let res = panic::catch_unwind(AssertUnwindSafe(|| once.call_once(|| {})));
+        assert!(res.is_err());
+    });
+    assert!(once.is_completed());
+}
+
+/// A backend whose context cannot be identified waits on a reentrant call, which only a
+/// deadline ends.
+#[cfg(any(feature = "spin", feature = "critical-section"))]
+pub(crate) fn unidentified_reentrancy_spins<R>()
+where
+    R: RawOnceTimed,
+{
+    let cell = OnceLock::<R, u32>::new();
+    let value = cell.get_or_init(|| {
+        let soon = Instant::now() + Duration::from_millis(10);
+        assert_eq!(
+            cell.try_get_or_init_timeout(|| 2, soon),
+            Err(OnceError::Failed(Timeout))
+        );
+        1
+    });
+    assert_eq!(value, &1);
+}
+
+pub(crate) fn exclusive_cell_hands_out_once<R>()
+where
+    R: RawOnce + Send + Sync,
+{
+    let cell = ExclusiveCell::<R, u32>::new();
+    assert_eq!(
+        cell.try_init(|| Err("failed")),
+        Err(TryInitError::Err("failed"))
+    );
+    assert!(!cell.is_initialized());
+
+    let res = panic::catch_unwind(AssertUnwindSafe(|| cell.init(|| panic!("boom"))));
+    assert!(res.is_err());
+    assert!(!cell.is_initialized());
+
+    let value = cell
+        .init(|| {
+            assert_eq!(
+                cell.try_init(|| Ok::<_, ()>(2)),
+                Err(TryInitError::Reentrant)
+            );
+            1
+        })
+        .unwrap();
+    *value += 1;
+    assert_eq!(*value, 2);
+    assert!(cell.is_initialized());
+    assert_eq!(cell.init(|| unreachable!()), None);
+    assert_eq!(
+        cell.try_init(|| Err::<_, ()>(())),
+        Err(TryInitError::AlreadyInitialized)
+    );
+
+    let cell = ExclusiveCell::<R, usize>::new();
+    let barrier = Barrier::new(THREADS);
+    let winners = thread::scope(|s| {
+        let handles: Vec<_> = (0..THREADS)
+            .map(|i| {
+                let cell = &cell;
+                let barrier = &barrier;
+                s.spawn(move || {
+                    barrier.wait();
+                    cell.init(|| i).is_some()
+                })
+            })
+            .collect();
+
+        handles
+            .into_iter()
+            .map(|h| h.join().unwrap())
+            .filter(|&won| won)
+            .count()
+    });
+    assert_eq!(winners, 1);
+}
+
+pub(crate) fn panic_poisons<R>()
+where
+    R: RawOnce,
+{
+    let once = Once::<R>::new();
+    let res = panic::catch_unwind(AssertUnwindSafe(|| once.call_once(|| panic!("boom"))));
+    assert!(res.is_err());
+    assert!(!once.is_completed());
+
+    let res = panic::catch_unwind(AssertUnwindSafe(|| once.call_once(|| {})));
+    assert!(res.is_err());
+
+    once.call_once_force(|state| assert!(state.is_poisoned()));
+    assert!(once.is_completed());
+
+    let cell = OnceLock::<R, u32>::new();
+    let res = panic::catch_unwind(AssertUnwindSafe(|| cell.get_or_init(|| panic!("boom"))));
+    assert!(res.is_err());
+    assert_eq!(cell.get(), None);
+    assert_eq!(cell.get_or_init(|| 3), &3);
+}
+
+pub(crate) fn poison_policies<R>()
+where
+    R: RawOnce,
+{
+    let once = Once::<R, IgnorePoison>::with_policy();
+    let res = panic::catch_unwind(AssertUnwindSafe(|| once.call_once(|| panic!("boom"))));
+    assert!(res.is_err());
+    once.call_once(|| {});
+    assert!(once.is_completed());
+
+    let once = Once::<R, ErrorOnPoison>::with_policy();
+    let res = panic::catch_unwind(AssertUnwindSafe(|| once.call_once(|| panic!("boom"))));
+    assert!(res.is_err());
+    assert_eq!(
+        once.try_call_once(|| Ok::<_, ()>(())),
+        Err(OnceError::Poisoned)
+    );
+    let res = panic::catch_unwind(AssertUnwindSafe(|| once.call_once(|| {})));
+    assert!(res.is_err());
+    assert!(!once.is_completed());
+
+    let cell = OnceLock::<R, u32, ErrorOnPoison>::with_policy();
+    let res = panic::catch_unwind(AssertUnwindSafe(|| cell.get_or_init(|| panic!("boom"))));
+    assert!(res.is_err());
+    assert_eq!(
+        cell.get_or_try_init(|| Ok::<_, ()>(1)),
+        Err(OnceError::Poisoned)
+    );
+    assert_eq!(cell.get(), None);
+}
+
+pub(crate) fn begin_init_completes_or_poisons<R>()
+where
+    R: RawOnceSplit + Send + Sync,
+{
+    let cell = OnceLock::<R, u32>::new();
+    match cell.begin_init() {
+        BeginInit::Uninitialized(guard) => drop(guard),
+        BeginInit::Initialized(_) => panic!("cell was initialized"),
+    }
+    assert_eq!(cell.get(), None);
+
+    let guard = match cell.begin_init() {
+        BeginInit::Uninitialized(guard) => guard,
+        BeginInit::Initialized(_) => panic!("cell was initialized"),
+    };
+    assert!(guard.state().is_poisoned());
+
+    thread::scope(|s| {
+        let waiter = s.spawn(|| *cell.get_or_init(|| 2));
+        assert_eq!(guard.complete(1), &1);
+        assert_eq!(waiter.join().unwrap(), 1);
+    });
+    assert!(matches!(cell.begin_init(), BeginInit::Initialized(&1)));
+
+    let cell = OnceLock::<R, u32, ErrorOnPoison>::with_policy();
+    match cell.begin_init() {
+        BeginInit::Uninitialized(guard) => drop(guard),
+        BeginInit::Initialized(_) => panic!("cell was initialized"),
+    }
+    assert!(matches!(cell.try_begin_init(), Err(OnceError::Poisoned)));
+}
+
+#[cfg(feature = "std")]
+pub(crate) fn poisoning_reports_attempts<R>()
+where
+    R: RawOnce,
+{
+    let once = Once::<RawAttemptsOnce<R>>::new();
+    let res = panic::catch_unwind(AssertUnwindSafe(|| once.call_once(|| panic!("boom"))));
+    assert!(res.is_err());
+
+    // Refusing to run on a poisoned once is not an attempt.
+    let payload = panic::catch_unwind(AssertUnwindSafe(|| once.call_once(|| {}))).unwrap_err();
+    let message = payload.downcast_ref::<String>().unwrap();
+    assert!(message.contains("attempt 2"), "{message}");
+    assert!(message.contains("boom"), "{message}");
+
+    once.call_once_force(|state| {
+        assert!(state.is_poisoned());
+        assert_eq!(state.attempt(), 2);
+        assert_eq!(state.panic_message(), Some("boom"));
+    });
+
+    // Only the wrapper keeps count.
+    let once = Once::<R>::new();
+    once.call_once_force(|state| assert_eq!(state.attempt(), 0));
+}
+
+pub(crate) fn lazy_panic_poisons<R>()
+where
+    R: RawOnce,
+{
+    let lazy = LazyLock::<R, u32>::new(|| panic!("boom"));
+    assert!(!LazyLock::is_poisoned(&lazy));
+    let res = panic::catch_unwind(AssertUnwindSafe(|| *lazy));
+    assert!(res.is_err());
+    assert!(LazyLock::is_poisoned(&lazy));
+    assert_eq!(LazyLock::try_force(&lazy), Err(OnceError::Poisoned));
+    let res = panic::catch_unwind(AssertUnwindSafe(|| *lazy));
+    assert!(res.is_err());
+
+    let mut lazy = LazyLock::<R, u32>::new(|| panic!("boom"));
+    let res = panic::catch_unwind(AssertUnwindSafe(|| *LazyLock::force_mut(&mut lazy)));
+    assert!(res.is_err());
+    assert!(LazyLock::is_poisoned(&lazy));
+    assert_eq!(LazyLock::try_force(&lazy), Err(OnceError::Poisoned));
+
+    let lazy = LazyLock::<R, u32>::new(|| 1);
+    assert_eq!(LazyLock::try_force(&lazy), Ok(&1));
+    assert!(!LazyLock::is_poisoned(&lazy));
+}
+
+pub(crate) fn lazy_shares_one_slot<R>()
+where
+    R: RawOnce,
+{
+    type Init = fn() -> u64;
+    // The value and the initializer used to be stored side by side, both optional.
+    let separate = mem::size_of::<(R, Option<u64>, Cell<Option<Init>>)>();
+    let shared = mem::size_of::<LazyLock<R, u64, Init>>();
+    assert_eq!(shared, mem::size_of::<(R, u64)>());
+    assert!(shared < separate);
+
+    let captured = Arc::new(());
+    let lazy = LazyLock::<R, Arc<()>, _>::new({
+        let captured = captured.clone();
+        move || captured
+    });
+    assert_eq!(Arc::strong_count(&captured), 2);
+    drop(lazy);
+    assert_eq!(Arc::strong_count(&captured), 1);
+
+    let lazy = LazyLock::<R, Arc<()>, _>::new({
+        let captured = captured.clone();
+        move || captured
+    });
+    assert!(Arc::ptr_eq(&lazy, &captured));
+    assert_eq!(Arc::strong_count(&captured), 2);
+    drop(lazy);
+    assert_eq!(Arc::strong_count(&captured), 1);
+
+    let lazy = LazyLock::<R, Arc<()>, _>::new({
+        let captured = captured.clone();
+        move || -> Arc<()> {
+            let _captured = captured;
+            panic!("boom")
+        }
+    });
+    let res = panic::catch_unwind(AssertUnwindSafe(|| LazyLock::force(&lazy).clone()));
+    assert!(res.is_err());
+    assert_eq!(Arc::strong_count(&captured), 1);
+    drop(lazy);
+    assert_eq!(Arc::strong_count(&captured), 1);
+}
+
+pub(crate) fn lazy_accessors_do_not_force<R>()
+where
+    R: RawOnce,
+{
+    let mut lazy = LazyLock::<R, u32>::new(|| 1);
+    assert_eq!(LazyLock::get(&lazy), None);
+    assert_eq!(LazyLock::get_mut(&mut lazy), None);
+    let init = LazyLock::into_inner(lazy).unwrap_err();
+    assert_eq!(init(), 1);
+
+    let mut lazy = LazyLock::<R, u32>::new(|| 1);
+    assert_eq!(*lazy, 1);
+    assert_eq!(LazyLock::get(&lazy), Some(&1));
+    *LazyLock::get_mut(&mut lazy).unwrap() = 2;
+    assert_eq!(LazyLock::into_inner(lazy).ok(), Some(2));
+
+    let lazy = LazyLock::<R, u32>::new(|| panic!("boom"));
+    let res = panic::catch_unwind(AssertUnwindSafe(|| *lazy));
+    assert!(res.is_err());
+    assert_eq!(LazyLock::get(&lazy), None);
+    let res = panic::catch_unwind(AssertUnwindSafe(|| LazyLock::into_inner(lazy)));
+    assert!(res.is_err());
+}
+
+pub(crate) fn try_lazy_retries_on_error<R>()
+where
+    R: RawOnce,
+{
+    let calls = AtomicUsize::new(0);
+    let lazy = TryLazyLock::<R, _, _, _, RetryOnError>::new(|| {
+        match calls.fetch_add(1, Ordering::Relaxed) {
+            0 => Err("failed"),
+            1 => panic!("boom"),
+            n => Ok(n),
+        }
+    });
+    assert_eq!(TryLazyLock::force(&lazy), Err("failed"));
+    assert_eq!(TryLazyLock::get(&lazy), None);
+    let res = panic::catch_unwind(AssertUnwindSafe(|| TryLazyLock::force(&lazy)));
+    assert!(res.is_err());
+    assert_eq!(TryLazyLock::force(&lazy), Ok(&2));
+    assert_eq!(TryLazyLock::force(&lazy), Ok(&2));
+    assert_eq!(TryLazyLock::get(&lazy), Some(&2));
+    assert_eq!(calls.load(Ordering::Relaxed), 3);
+
+    let value = Arc::new(());
+    let init = value.clone();
+    let lazy = TryLazyLock::<R, _, (), _, RetryOnError>::new(move || Ok(init.clone()));
+    assert!(TryLazyLock::force(&lazy).is_ok());
+    // The initializer is dropped as soon as the value replaces it.
+    assert_eq!(Arc::strong_count(&value), 2);
+    drop(lazy);
+    assert_eq!(Arc::strong_count(&value), 1);
+
+    let init = value.clone();
+    let lazy = TryLazyLock::<R, (), (), _, RetryOnError>::new(move || {
+        let _ = &init;
+        Ok(())
+    });
+    drop(lazy);
+    assert_eq!(Arc::strong_count(&value), 1);
+}
+
+pub(crate) fn try_lazy_caches_the_first_error<R>()
+where
+    R: RawOnce,
+{
+    let calls = AtomicUsize::new(0);
+    let lazy = TryLazyLock::<R, u32, _, _, CacheError>::new(|| {
+        calls.fetch_add(1, Ordering::Relaxed);
+        Err(String::from("failed"))
+    });
+    assert_eq!(TryLazyLock::force(&lazy), Err(String::from("failed")));
+    assert_eq!(TryLazyLock::force(&lazy), Err(String::from("failed")));
+    assert_eq!(TryLazyLock::get(&lazy), None);
+    assert_eq!(calls.load(Ordering::Relaxed), 1);
+
+    let error = Arc::new(());
+    let init = error.clone();
+    let lazy = TryLazyLock::<R, u32, _, _, CacheError>::new(move || Err(init));
+    assert!(TryLazyLock::force(&lazy).is_err());
+    assert_eq!(Arc::strong_count(&error), 2);
+    drop(lazy);
+    assert_eq!(Arc::strong_count(&error), 1);
+
+    let lazy = TryLazyLock::<R, u32, (), _, CacheError>::new(|| Ok(1));
+    assert_eq!(TryLazyLock::force(&lazy), Ok(&1));
+    assert_eq!(TryLazyLock::get(&lazy), Some(&1));
+
+    // The initializer is dropped exactly once, whether it never runs or panics.
+    let init = error.clone();
+    let lazy = TryLazyLock::<R, u32, (), _, CacheError>::new(move || {
+        let _ = &init;
+        Ok(1)
+    });
+    drop(lazy);
+    assert_eq!(Arc::strong_count(&error), 1);
+
+    let init = error.clone();
+    let lazy = TryLazyLock::<R, u32, (), _, CacheError>::new(move || {
+        let _init = init;
+        panic!("boom")
+    });
+    let res = panic::catch_unwind(AssertUnwindSafe(|| TryLazyLock::force(&lazy)));
+    assert!(res.is_err());
+    assert_eq!(Arc::strong_count(&error), 1);
+    let res = panic::catch_unwind(AssertUnwindSafe(|| TryLazyLock::force(&lazy)));
+    assert!(res.is_err());
+    drop(lazy);
+    assert_eq!(Arc::strong_count(&error), 1);
+}
+
+pub(crate) fn wait_blocks_until_initialized<R>()
+where
+    R: RawOnceWait + Send + Sync,
+{
+    let cell = OnceLock::<R, u32>::new();
+    let once = Once::<R>::new();
+
+    thread::scope(|s| {
+        let waiters: Vec<_> = (0..THREADS)
+            .map(|_| s.spawn(|| (*cell.wait(), once.wait())))
+            .collect();
+
+        thread::sleep(Duration::from_millis(10));
+        assert_eq!(cell.set(5), Ok(()));
+        once.call_once(|| {});
+
+        for waiter in waiters {
+            assert_eq!(waiter.join().unwrap(), (5, ()));
+        }
+    });
+}
+
+pub(crate) fn wait_observes_poisoning<R>()
+where
+    R: RawOnceWait + Send + Sync,
+{
+    let once = Once::<R>::new();
+
+    thread::scope(|s| {
+        let waiter = s.spawn(|| once.wait());
+        let forced = s.spawn(|| once.wait_force());
+
+        thread::sleep(Duration::from_millis(10));
+        let res = panic::catch_unwind(AssertUnwindSafe(|| once.call_once(|| panic!("boom"))));
+        assert!(res.is_err());
+        assert!(waiter.join().is_err());
+
+        once.call_once_force(|_| {});
+        forced.join().unwrap();
+    });
+}
+
+pub(crate) fn wait_timeout_expires<R>()
+where
+    R: RawOnceTimed,
+{
+    let cell = OnceLock::<R, u32>::new();
+    let soon = Instant::now() + Duration::from_millis(10);
+    assert_eq!(cell.wait_timeout(soon), Err(Timeout));
+
+    assert_eq!(cell.set(1), Ok(()));
+    assert_eq!(cell.wait_timeout(Instant::now()), Ok(&1));
+}
+
+pub(crate) fn timeout_while_running<R>()
+where
+    R: RawOnceTimed + Send + Sync,
+{
+    let cell = OnceLock::<R, u32>::new();
+    let once = Once::<R>::new();
+    let barrier = Barrier::new(2);
+
+    thread::scope(|s| {
+        s.spawn(|| {
+            cell.get_or_init(|| {
+                barrier.wait();
+                thread::sleep(Duration::from_millis(100));
+                1
+            });
+            once.call_once(|| {
+                barrier.wait();
+                thread::sleep(Duration::from_millis(100));
+            });
+        });
+
+        barrier.wait();
+        let soon = Instant::now() + Duration::from_millis(10);
+        assert_eq!(cell.get_or_init_timeout(|| 2, soon), Err(Timeout));
+        assert_eq!(cell.wait_timeout(soon), Err(Timeout));
+        let later = Instant::now() + Duration::from_secs(10);
+        assert_eq!(cell.get_or_init_timeout(|| 2, later), Ok(&1));
+
+        barrier.wait();
+        let soon = Instant::now() + Duration::from_millis(10);
+        assert_eq!(
+            once.call_once_timeout(|| unreachable!(), soon),
+            Err(Timeout)
+        );
+        let later = Instant::now() + Duration::from_secs(10);
+        assert_eq!(once.call_once_timeout(|| unreachable!(), later), Ok(()));
+    });
+}
+
+pub(crate) fn nonblocking_would_block_while_running<R>()
+where
+    R: RawOnceNonBlocking,
+{
+    let cell = OnceLock::<R, u32>::new();
+    let value = cell.get_or_init(|| {
+        assert_eq!(
+            cell.try_get_or_init_nonblocking(|| unreachable!()),
+            Err(OnceError::Failed(WouldBlock))
+        );
+        1
+    });
+    assert_eq!(value, &1);
+    assert_eq!(cell.try_get_or_init_nonblocking(|| unreachable!()), Ok(&1));
+
+    let cell = OnceLock::<R, u32>::new();
+    assert_eq!(cell.try_get_or_init_nonblocking(|| 2), Ok(&2));
+
+    let once = Once::<R>::new();
+    once.call_once(|| {
+        assert_eq!(
+            once.try_call_once_nonblocking(|| unreachable!()),
+            Err(OnceError::Failed(WouldBlock))
+        );
+    });
+    assert_eq!(once.try_call_once_nonblocking(|| unreachable!()), Ok(()));
+}
+
+pub(crate) fn try_apis_do_not_panic<R>()
+where
+    R: RawOnceTimed + RawOnceNonBlocking,
+{
+    let later = Instant::now() + Duration::from_secs(10);
+
+    let once = Once::<R, ErrorOnPoison>::with_policy();
+    let res = panic::catch_unwind(AssertUnwindSafe(|| {
+        once.call_once_force(|_| panic!("boom"));
+    }));
+    assert!(res.is_err());
+    assert_eq!(once.try_wait(), Err(OnceError::Poisoned));
+    assert_eq!(
+        once.try_call_once_timeout(|| unreachable!(), later),
+        Err(OnceError::Poisoned)
+    );
+    assert_eq!(
+        once.try_call_once_nonblocking(|| unreachable!()),
+        Err(OnceError::Poisoned)
+    );
+    once.call_once_force(|_| {});
+    assert_eq!(once.try_wait(), Ok(()));
+    assert_eq!(once.try_wait_force(), Ok(()));
+
+    let cell = OnceLock::<R, u32, ErrorOnPoison>::with_policy();
+    let res = panic::catch_unwind(AssertUnwindSafe(|| cell.get_or_init(|| panic!("boom"))));
+    assert!(res.is_err());
+    assert_eq!(
+        cell.try_get_or_init_timeout(|| unreachable!(), later),
+        Err(OnceError::Poisoned)
+    );
+    assert_eq!(
+        cell.try_get_or_init_nonblocking(|| unreachable!()),
+        Err(OnceError::Poisoned)
+    );
+    assert_eq!(cell.try_set(1), Ok(()));
+    assert_eq!(cell.try_set(2), Err(OnceError::Failed(2)));
+    assert_eq!(cell.try_insert_checked(3), Err(OnceError::Failed((&1, 3))));
+    assert_eq!(cell.try_wait(), Ok(&1));
+    assert_eq!(cell.try_wait_timeout(later), Ok(&1));
+
+    let mut lazy = LazyLock::<R, u32>::new(|| panic!("boom"));
+    let res = panic::catch_unwind(AssertUnwindSafe(|| *lazy));
+    assert!(res.is_err());
+    assert_eq!(LazyLock::try_force_mut(&mut lazy), Err(OnceError::Poisoned));
+    assert!(matches!(
+        LazyLock::try_into_inner(lazy),
+        Err(OnceError::Poisoned)
+    ));
+    let lazy = LazyLock::<R, u32>::new(|| 1);
+    assert!(matches!(
+        LazyLock::try_into_inner(lazy),
+        Err(OnceError::Failed(_))
+    ));
+
+    let lazy = TryLazyLock::<R, u32, (), _, CacheError>::new(|| panic!("boom"));
+    let res = panic::catch_unwind(AssertUnwindSafe(|| TryLazyLock::force(&lazy)));
+    assert!(res.is_err());
+    assert_eq!(TryLazyLock::try_force(&lazy), Err(OnceError::Poisoned));
+}
+
+pub(crate) fn async_races_to_a_single_value<R>()
+where
+    R: RawOnce + Send + Sync,
+{
+    let cell = AsyncOnceLock::<R, usize>::new();
+    let barrier = Barrier::new(THREADS);
+
+    let values: Vec<usize> = thread::scope(|s| {
+        let handles: Vec<_> = (0..THREADS)
+            .map(|i| {
+                let cell = &cell;
+                let barrier = &barrier;
+                s.spawn(move || {
+                    barrier.wait();
+                    *block_on(cell.get_or_init(|| async move {
+                        thread::sleep(Duration::from_millis(10));
+                        i
+                    }))
+                })
+            })
+            .collect();
+
+        handles.into_iter().map(|h| h.join().unwrap()).collect()
+    });
+
+    let winner = values[0];
+    assert!(values.iter().all(|&v| v == winner));
+    assert_eq!(cell.get(), Some(&winner));
+    assert_eq!(block_on(cell.wait()), &winner);
+}
+
+pub(crate) fn async_cancelled_init_is_taken_over<R>()
+where
+    R: RawOnce,
+{
+    let wakes = Arc::new(CountingWaker(AtomicUsize::new(0)));
+    let waker = Waker::from(wakes.clone());
+    let mut cx = task::Context::from_waker(&waker);
+
+    let cell = AsyncOnceLock::<R, u32>::new();
+    let mut first = Box::pin(cell.get_or_init(future::pending));
+    assert!(first.as_mut().poll(&mut cx).is_pending());
+    let mut second = pin!(cell.get_or_init(|| async { 2 }));
+    assert!(second.as_mut().poll(&mut cx).is_pending());
+    let mut waiter = pin!(cell.wait());
+    assert!(waiter.as_mut().poll(&mut cx).is_pending());
+
+    drop(first);
+    assert_eq!(wakes.0.load(Ordering::Relaxed), 2);
+    assert!(waiter.as_mut().poll(&mut cx).is_pending());
+    assert_eq!(second.as_mut().poll(&mut cx), Poll::Ready(&2));
+    assert_eq!(wakes.0.load(Ordering::Relaxed), 3);
+    assert_eq!(waiter.as_mut().poll(&mut cx), Poll::Ready(&2));
+
+    let cell = AsyncOnceLock::<R, u32>::new();
+    assert_eq!(
+        block_on(cell.get_or_try_init(|| async { Err("failed") })),
+        Err("failed")
+    );
+    assert_eq!(cell.get(), None);
+
+    let mut pending = true;
+    let lazy = AsyncLazy::<R, u32, _>::new(|| {
+        let pending = mem::replace(&mut pending, false);
+        async move {
+            if pending {
+                future::pending::<()>().await;
+            }
+            3
+        }
+    });
+    let mut first = Box::pin(lazy.force());
+    assert!(first.as_mut().poll(&mut cx).is_pending());
+    drop(first);
+    assert_eq!(lazy.get(), None);
+    assert_eq!(block_on(lazy.force()), &3);
+}
+
+pub(crate) fn async_wake_can_drop_the_waiter<R>()
+where
+    R: RawOnce + Send + Sync + 'static,
+{
+    let cell: &'static AsyncOnceLock<R, u32> = Box::leak(Box::new(AsyncOnceLock::new()));
+    let waker = Arc::new(DroppingWaker(Mutex::new(Some(Box::pin(cell.wait())))));
+    waker.poll_pending();
+
+    assert_eq!(block_on(cell.get_or_init(|| async { 1 })), &1);
+    assert!(waker.0.lock().unwrap().is_none());
+}
+
+pub(crate) fn notify_wakes_async_waiters<R>()
+where
+    R: RawOnce + Send + Sync,
+{
+    let cell = OnceLock::<RawNotifyOnce<R>, u32>::new();
+    thread::scope(|s| {
+        s.spawn(|| {
+            thread::sleep(Duration::from_millis(10));
+            assert_eq!(cell.set(1), Ok(()));
+        });
+        assert_eq!(block_on(cell.wait_async()), &1);
+    });
+
+    let wakes = Arc::new(CountingWaker(AtomicUsize::new(0)));
+    let waker = Waker::from(wakes.clone());
+    let mut cx = task::Context::from_waker(&waker);
+
+    let cell = OnceLock::<RawNotifyOnce<R>, u32>::new();
+    let mut waiter = pin!(cell.wait_async());
+    assert!(waiter.as_mut().poll(&mut cx).is_pending());
+
+    let res = panic::catch_unwind(AssertUnwindSafe(|| cell.get_or_init(|| panic!("boom"))));
+    assert!(res.is_err());
+    assert_eq!(wakes.0.load(Ordering::Relaxed), 1);
+    assert!(waiter.as_mut().poll(&mut cx).is_pending());
+
+    assert_eq!(cell.set(2), Ok(()));
+    assert_eq!(wakes.0.load(Ordering::Relaxed), 2);
+    assert_eq!(waiter.as_mut().poll(&mut cx), Poll::Ready(&2));
+}
+
+pub(crate) fn notify_wake_can_drop_the_waiter<R>()
+where
+    R: RawOnce + Send + Sync + 'static,
+{
+    let cell: &'static OnceLock<RawNotifyOnce<R>, u32> = Box::leak(Box::new(OnceLock::new()));
+    let waker = Arc::new(DroppingWaker(Mutex::new(Some(Box::pin(cell.wait_async())))));
+    waker.poll_pending();
+
+    assert_eq!(cell.set(1), Ok(()));
+    assert!(waker.0.lock().unwrap().is_none());
+}
+
+struct CountingWaker(AtomicUsize);
+
+impl Wake for CountingWaker {
+    fn wake(self: Arc<Self>) {
+        self.0.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// Drops the future it was polled with when woken, like an executor cancelling the task
+/// from its waker.
+struct DroppingWaker(Mutex<Option<BoxedWait>>);
+
+type BoxedWait = Pin<Box<dyn Future<Output = &'static u32> + Send>>;
+
+impl DroppingWaker {
+    fn poll_pending(self: &Arc<Self>) {
+        let waker = Waker::from(self.clone());
+        let mut cx = task::Context::from_waker(&waker);
+        let mut fut = self.0.lock().unwrap();
+        assert!(fut.as_mut().unwrap().as_mut().poll(&mut cx).is_pending());
+    }
+}
+
+impl Wake for DroppingWaker {
+    fn wake(self: Arc<Self>) {
+        let fut = self.0.lock().unwrap().take();
+        drop(fut);
+    }
+}
+
+struct ThreadWaker(Thread);
+
+impl Wake for ThreadWaker {
+    fn wake(self: Arc<Self>) {
+        self.0.unpark();
+    }
+}
+
+fn block_on<F>(fut: F) -> F::Output
+where
+    F: Future,
+{
+    let mut fut = pin!(fut);
+    let waker = Waker::from(Arc::new(ThreadWaker(thread::current())));
+    let mut cx = task::Context::from_waker(&waker);
+
+    loop {
+        match fut.as_mut().poll(&mut cx) {
+            Poll::Ready(value) => return value,
+            Poll::Pending => thread::park(),
+        }
+    }
+}
@@ -0,0 +1,45 @@
+use core::{fmt, time::Duration};
+
+/// A point in time at which a blocking operation gives up.
+///
+/// All a backend needs is the time left, so `no_std` code can implement this on top of
+/// whatever clock the platform provides. With the `std` feature it is implemented for
+#[cfg_attr(
+    feature = "std",
+    doc = "[`std::time::Instant`](libstd::time::Instant)."
+)]
+#[cfg_attr(not(feature = "std"), doc = "`std::time::Instant`.")]
+pub trait Deadline {
+    /// The time left before the deadline, or `None` if it has passed.
+    fn remaining(&self) -> Option<Duration>;
+}
+
+impl<D> Deadline for &D
+where
+    D: Deadline + ?Sized,
+{
+    fn remaining(&self) -> Option<Duration> {
+        (**self).remaining()
+    }
+}
+
+#[cfg(any(feature = "std", test))]
+impl Deadline for libstd::time::Instant {
+    fn remaining(&self) -> Option<Duration> {
+        self.checked_duration_since(libstd::time::Instant::now())
+            .filter(|remaining| !remaining.is_zero())
+    }
+}
+
+/// The deadline passed before the once completed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Timeout;
+
+impl fmt::Display for Timeout {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("deadline passed before the once completed")
+    }
+}
+
+#[cfg(feature = "std")]
+impl libstd::error::Error for Timeout {}
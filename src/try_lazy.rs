@@ -0,0 +1,199 @@
+use core::{cell::UnsafeCell, marker::PhantomData, mem::ManuallyDrop};
+
+use crate::{
+    error::{flatten, reentrant},
+    OnceError, OnceLock, RawOnce,
+};
+
+/// A [`TryLazyLock`] policy that runs the initializer again on the next access after it
+/// fails.
+///
+/// The initializer may be called many times, so it must be an [`FnMut`].
+pub struct RetryOnError;
+
+/// A [`TryLazyLock`] policy that keeps the first error and returns a clone of it on every
+/// later access, without running the initializer again.
+///
+/// The error is cloned each time it is returned, so an error that is expensive to clone is
+/// best shared behind an `Arc`.
+pub struct CacheError;
+
+/// A value that is initialized on first access by an initializer that may fail.
+///
+/// `P` decides what happens after a failure, see [`RetryOnError`] and [`CacheError`]. The
+/// outcome is kept in a [`OnceLock`], which stores just the value with [`RetryOnError`],
+/// and the value or the first error with [`CacheError`].
+pub struct TryLazyLock<R, T, E, F, P = RetryOnError>
+where
+    R: RawOnce,
+    P: TryLazyPolicy<T, E, F>,
+{
+    cell: OnceLock<R, P::Value>,
+    // Moved out by the call that no longer needs it, see `TryLazyPolicy::RUN_TAKES_INIT`.
+    init: UnsafeCell<ManuallyDrop<F>>,
+    _policy: PhantomData<fn() -> P>,
+}
+
+impl<R, T, E, F, P> TryLazyLock<R, T, E, F, P>
+where
+    R: RawOnce,
+    P: TryLazyPolicy<T, E, F>,
+{
+    pub const fn new(init: F) -> Self {
+        Self {
+            cell: OnceLock::new(),
+            init: UnsafeCell::new(ManuallyDrop::new(init)),
+            _policy: PhantomData,
+        }
+    }
+
+    /// The value, if it has been initialized successfully, without forcing it.
+    pub fn get(this: &Self) -> Option<&T> {
+        this.cell.get().and_then(P::value)
+    }
+
+    /// Force the value, running the initializer if no earlier access has succeeded, and
+    /// handling its errors as chosen by `P`.
+    ///
+    /// # Panics
+    /// Panics if called from within the initializer, or if `P` is [`CacheError`] and the
+    /// initializer panicked on an earlier access.
+    pub fn force(this: &Self) -> Result<&T, E> {
+        match Self::try_force(this) {
+            Ok(value) => Ok(value),
+            Err(OnceError::Failed(err)) => Err(err),
+            Err(OnceError::Reentrant) => reentrant(),
+            Err(OnceError::Poisoned) => {
+                panic!("TryLazyLock instance has previously been poisoned")
+            }
+        }
+    }
+
+    /// Like [`TryLazyLock::force`], but reports poisoning and reentrancy instead of
+    /// panicking.
+    pub fn try_force(this: &Self) -> Result<&T, OnceError<E>> {
+        P::try_force(this)
+    }
+}
+
+/// How a [`TryLazyLock`] handles a failing initializer.
+///
+/// This is implemented by [`RetryOnError`] and [`CacheError`], and cannot be implemented
+/// outside of this crate.
+pub trait TryLazyPolicy<T, E, F>: Sized + sealed::Sealed {
+    /// What the cell of the lazy holds once it is initialized.
+    #[doc(hidden)]
+    type Value;
+
+    /// Whether the initializer is moved out to be run, rather than only once it succeeds.
+    /// Either way, it is gone once the cell is initialized, and with this set it is also
+    /// gone once the cell is poisoned.
+    #[doc(hidden)]
+    const RUN_TAKES_INIT: bool;
+
+    #[doc(hidden)]
+    fn value(value: &Self::Value) -> Option<&T>;
+
+    #[doc(hidden)]
+    fn try_force<R>(lazy: &TryLazyLock<R, T, E, F, Self>) -> Result<&T, OnceError<E>>
+    where
+        R: RawOnce;
+}
+
+impl<T, E, F> TryLazyPolicy<T, E, F> for RetryOnError
+where
+    F: FnMut() -> Result<T, E>,
+{
+    type Value = T;
+
+    const RUN_TAKES_INIT: bool = false;
+
+    fn value(value: &T) -> Option<&T> {
+        Some(value)
+    }
+
+    fn try_force<R>(lazy: &TryLazyLock<R, T, E, F, Self>) -> Result<&T, OnceError<E>>
+    where
+        R: RawOnce,
+    {
+        // The initializer stays in place until it succeeds, so one that failed or panicked
+        // is simply run again, as the cell ignores poisoning. It is dropped once the value
+        // is published, so that a panicking destructor cannot poison the initialized cell.
+        let mut done = None;
+        let res = lazy.cell.get_or_try_init(|| {
+            // Only the call running the once gets here, so it has the initializer to
+            // itself.
+            let init = unsafe { &mut *lazy.init.get() };
+            let value = (**init)()?;
+            done = Some(unsafe { ManuallyDrop::take(init) });
+            Ok(value)
+        });
+        drop(done);
+        res
+    }
+}
+
+impl<T, E, F> TryLazyPolicy<T, E, F> for CacheError
+where
+    F: FnOnce() -> Result<T, E>,
+    E: Clone,
+{
+    type Value = Result<T, E>;
+
+    const RUN_TAKES_INIT: bool = true;
+
+    fn value(value: &Result<T, E>) -> Option<&T> {
+        value.as_ref().ok()
+    }
+
+    fn try_force<R>(lazy: &TryLazyLock<R, T, E, F, Self>) -> Result<&T, OnceError<E>>
+    where
+        R: RawOnce,
+    {
+        let res = lazy.cell.get_or_try_init_force(|state| {
+            // The initializer is taken by the call that runs it. If it panicked, there is
+            // nothing left to run.
+            if state.is_poisoned() {
+                state.set_skipped(true);
+                return Err(OnceError::Poisoned);
+            }
+            let init = unsafe { ManuallyDrop::take(&mut *lazy.init.get()) };
+            Ok(init())
+        });
+
+        match flatten(res)? {
+            Ok(value) => Ok(value),
+            Err(err) => Err(OnceError::Failed(err.clone())),
+        }
+    }
+}
+
+mod sealed {
+    pub trait Sealed {}
+
+    impl Sealed for super::RetryOnError {}
+    impl Sealed for super::CacheError {}
+}
+
+impl<R, T, E, F, P> Drop for TryLazyLock<R, T, E, F, P>
+where
+    R: RawOnce,
+    P: TryLazyPolicy<T, E, F>,
+{
+    fn drop(&mut self) {
+        let taken = self.cell.get().is_some() || (P::RUN_TAKES_INIT && self.cell.is_poisoned());
+        if !taken {
+            unsafe { ManuallyDrop::drop(self.init.get_mut()) };
+        }
+    }
+}
+
+unsafe impl<R, T, E, F, P> Sync for TryLazyLock<R, T, E, F, P>
+where
+    P: TryLazyPolicy<T, E, F>,
+    T: Sync + Send,
+    E: Sync + Send,
+    R: RawOnce + Sync + Send,
+    F: Send,
+{
+}
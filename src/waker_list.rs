@@ -0,0 +1,180 @@
+//! An intrusive list of tasks waiting for a once, for the async types.
+//!
+//! Each waiting future carries its own node, so waiting needs no allocation. The list is
+//! guarded by a lock that is only ever held for a few pointer updates, never while a waker
+//! runs, since waking may drop the very future that is being woken.
+//!
+//! With the `critical-section` feature the lock is a critical section, so tasks may be
+//! registered and woken from interrupts. Otherwise it is a spin lock, which deadlocks if
+//! one holder of it preempts another on the same core.
+
+use core::{cell::UnsafeCell, marker::PhantomPinned, pin::Pin, ptr, task::Waker};
+#[cfg(not(feature = "critical-section"))]
+use core::{
+    hint,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+pub(crate) struct WakerList {
+    #[cfg(not(feature = "critical-section"))]
+    locked: AtomicBool,
+    head: UnsafeCell<*mut Node>,
+}
+
+impl WakerList {
+    pub const fn new() -> Self {
+        Self {
+            #[cfg(not(feature = "critical-section"))]
+            locked: AtomicBool::new(false),
+            head: UnsafeCell::new(ptr::null_mut()),
+        }
+    }
+
+    /// Wakes and dequeues every registered task, including tasks that register while it
+    /// runs.
+    pub fn wake_all(&self) {
+        while let Some(waker) = self.pop() {
+            waker.wake();
+        }
+    }
+
+    /// Dequeues the first registered task, returning its waker if it has one.
+    ///
+    /// Once unlinked, the node is no longer touched, so its waiter is free to be dropped
+    /// as soon as the lock is released.
+    fn pop(&self) -> Option<Waker> {
+        self.with_lock(|| unsafe {
+            loop {
+                let node = *self.head.get();
+                if node.is_null() {
+                    return None;
+                }
+
+                let next = (*node).next;
+                if !next.is_null() {
+                    (*next).prev = ptr::null_mut();
+                }
+                *self.head.get() = next;
+                (*node).next = ptr::null_mut();
+                (*node).queued = false;
+
+                if let Some(waker) = (*node).waker.take() {
+                    return Some(waker);
+                }
+            }
+        })
+    }
+
+    #[cfg(feature = "critical-section")]
+    fn with_lock<T>(&self, f: impl FnOnce() -> T) -> T {
+        critical_section::with(|_| f())
+    }
+
+    #[cfg(not(feature = "critical-section"))]
+    fn with_lock<T>(&self, f: impl FnOnce() -> T) -> T {
+        while self
+            .locked
+            .compare_exchange_weak(false, true, Ordering::Acquire, Ordering::Relaxed)
+            .is_err()
+        {
+            while self.locked.load(Ordering::Relaxed) {
+                hint::spin_loop();
+            }
+        }
+        let _lock = Lock { list: self };
+        f()
+    }
+}
+
+unsafe impl Send for WakerList {}
+unsafe impl Sync for WakerList {}
+
+/// Unlocks the list even if the holder of the lock panics.
+#[cfg(not(feature = "critical-section"))]
+struct Lock<'a> {
+    list: &'a WakerList,
+}
+
+#[cfg(not(feature = "critical-section"))]
+impl Drop for Lock<'_> {
+    fn drop(&mut self) {
+        self.list.locked.store(false, Ordering::Release);
+    }
+}
+
+/// Only ever accessed with the list locked.
+struct Node {
+    waker: Option<Waker>,
+    prev: *mut Node,
+    next: *mut Node,
+    queued: bool,
+}
+
+/// A waiting task's place in a [`WakerList`].
+///
+/// It is removed from the list when dropped, which is why it has to be pinned while it is
+/// registered.
+pub(crate) struct Waiter<'a> {
+    list: &'a WakerList,
+    node: UnsafeCell<Node>,
+    _pinned: PhantomPinned,
+}
+
+impl<'a> Waiter<'a> {
+    pub const fn new(list: &'a WakerList) -> Self {
+        Self {
+            list,
+            node: UnsafeCell::new(Node {
+                waker: None,
+                prev: ptr::null_mut(),
+                next: ptr::null_mut(),
+                queued: false,
+            }),
+            _pinned: PhantomPinned,
+        }
+    }
+
+    /// Queues the task to be woken by the next [`WakerList::wake_all`], replacing the
+    /// waker of any earlier registration.
+    pub fn register(self: Pin<&Self>, waker: &Waker) {
+        self.list.with_lock(|| unsafe {
+            let node = self.node.get();
+            match &(*node).waker {
+                Some(old) if old.will_wake(waker) => {}
+                _ => (*node).waker = Some(waker.clone()),
+            }
+
+            if !(*node).queued {
+                let head = self.list.head.get();
+                (*node).next = *head;
+                if !(*head).is_null() {
+                    (**head).prev = node;
+                }
+                *head = node;
+                (*node).queued = true;
+            }
+        })
+    }
+}
+
+impl Drop for Waiter<'_> {
+    fn drop(&mut self) {
+        self.list.with_lock(|| unsafe {
+            let node = self.node.get();
+            if !(*node).queued {
+                return;
+            }
+            if (*node).prev.is_null() {
+                *self.list.head.get() = (*node).next;
+            } else {
+                (*(*node).prev).next = (*node).next;
+            }
+            if !(*node).next.is_null() {
+                (*(*node).next).prev = (*node).prev;
+            }
+        })
+    }
+}
+
+// Waiters only touch their node with the list locked.
+unsafe impl Send for Waiter<'_> {}